@@ -0,0 +1,851 @@
+// Building customised games
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rand::prelude::*;
+
+use crate::campaign;
+use crate::clock::{Clock, SystemClock};
+use crate::game::{
+    AppleStyle, Borders, Direction, Edge, Edges, GameState, Keys, QueueOverflow, Region, Ripple,
+    RivalSkill, Segment, SegmentType, ShrinkPolicy, SnakeStyle, MAX_BOARD,
+};
+use crate::level::Level;
+use crate::obstacles;
+use crate::position::Position;
+use crate::render::fit_board;
+use crate::stats::Mode;
+
+// How long a time attack lasts when no time limit is given, in seconds as
+// Duration::from_mins is too new for older toolchains
+#[allow(clippy::duration_suboptimal_units)]
+const TIME_ATTACK_LIMIT: Duration = Duration::from_secs(60);
+
+// Tick delay in hardcore mode, every hardcore game is played at this speed
+const HARDCORE_DELAY: Duration = Duration::from_millis(150);
+
+/// A game that can't be played
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildError {
+    EmptyBoard,
+    SnakeOutOfBounds,
+    NoRoomForApple,
+    WrapTargetOutOfBounds,
+    SpawnRegionOutOfBounds,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::EmptyBoard => write!(f, "the board has no cells"),
+            BuildError::SnakeOutOfBounds => write!(f, "the snake doesn't fit on the board"),
+            BuildError::NoRoomForApple => write!(f, "there's no free cell for the apple"),
+            BuildError::WrapTargetOutOfBounds => write!(f, "an edge wraps to a cell off the board"),
+            BuildError::SpawnRegionOutOfBounds => {
+                write!(f, "the apple spawn region isn't all on the board")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Builds a [`GameState`], starting from the defaults used by
+/// [`GameState::new`]
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct GameStateBuilder {
+    pub(crate) state: GameState,
+    obstacles: usize,
+    obstacle_gap: u16,
+    apple: Option<(u16, u16)>,
+    swarm: usize,
+}
+
+impl Default for GameStateBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameStateBuilder {
+    // Every setting's default, one line each
+    #[allow(clippy::too_many_lines)]
+    pub fn new() -> Self {
+        GameStateBuilder {
+            obstacles: 0,
+            obstacle_gap: 1,
+            apple: None,
+            swarm: 0,
+            state: GameState {
+                snake: {
+                    let mut v = VecDeque::new();
+                    v.push_back(Segment(0, 0, SegmentType::EastWest, Direction::East));
+                    v.push_back(Segment(1, 0, SegmentType::EastWest, Direction::East));
+                    v
+                },
+                delay: Duration::from_millis(250),
+                apple: (5, 5),
+                head: (1, 0),
+                board: (10, 10),
+                direction: Direction::East,
+                snake_style: SnakeStyle::CurvedLine,
+                apple_style: AppleStyle::Filled,
+                edges: Edges::all(Edge::Wall),
+                color: true,
+                teleport: false,
+                chase: 0,
+                score: 0,
+                apples: 0,
+                start: Instant::now(),
+                magnet: None,
+                magnet_ticks: 0,
+                magnet_rate: 0.0,
+                square: false,
+                time_limit: None,
+                compass: false,
+                seed: random(),
+                apple_age: 0,
+                apple_timeout: None,
+                quit: false,
+                confirm_quit: false,
+                walls: HashSet::new(),
+                wrap_blocked: false,
+                colorblind: false,
+                scroll: None,
+                shrink_every: None,
+                rings: 0,
+                base_delay: Duration::from_millis(250),
+                speed_bonus: 0.5,
+                blur: false,
+                afterimage: None,
+                idle_pause: None,
+                last_input: Instant::now(),
+                idle: false,
+                show_position: false,
+                hardcore: false,
+                eat_flash: 1,
+                flash: 0,
+                ghost_rate: 0.0,
+                ghost: None,
+                rivals: Vec::new(),
+                spare_apples: Vec::new(),
+                swarm: 0,
+                turns: VecDeque::new(),
+                immediate_turns: false,
+                show_length: false,
+                rewinds: 0,
+                rewind_depth: 5,
+                fixed_length: None,
+                streak: 0,
+                boost: 0.0,
+                streak_boost: 0.0,
+                streak_decay: 0.02,
+                shrink_policy: ShrinkPolicy::Refuse,
+                confirm_shrink: None,
+                shrink_rng: StdRng::seed_from_u64(0),
+                zones: Vec::new(),
+                blocks: HashSet::new(),
+                fatal_blocks: false,
+                wall_grace: 0,
+                grace: None,
+                void: HashSet::new(),
+                breadcrumb_ticks: 0,
+                breadcrumbs: Vec::new(),
+                clock: Arc::new(SystemClock),
+                reversed: false,
+                mirror: None,
+                mirror_ticks: 0,
+                mirror_rate: 0.0,
+                milestones: Vec::new(),
+                milestone: None,
+                cheer: 0,
+                spawn_region: None,
+                shake: false,
+                shaking: 0,
+                close_call: false,
+                rulers: false,
+                ripple: None,
+                ripple_delay: Duration::from_millis(40),
+                rippled: None,
+                accept_paste: false,
+                notice: None,
+                autopilot: false,
+                turn_queue: 2,
+                queue_overflow: QueueOverflow::DropNewest,
+                lookahead: 0,
+                apple_inset: 0,
+                title: String::new(),
+                footer: String::new(),
+                wrap_arrows: false,
+                shield_ticks: 0,
+                shield: 0,
+                borders: Borders::default(),
+                paused: None,
+                keys: Keys::default(),
+                fit_terminal: None,
+                speed_step: Duration::ZERO,
+                speed_every: 5,
+                speed_floor: Duration::from_millis(60),
+                stage: None,
+                apple_goal: None,
+                rival_skill: RivalSkill::Normal,
+                tron: false,
+                daily: false,
+                attract: false,
+                attracting: false,
+            },
+        }
+    }
+
+    pub fn board(mut self, width: u16, height: u16) -> Self {
+        self.state.board = (width, height);
+        self
+    }
+
+    pub fn delay(mut self, ms: u64) -> Self {
+        self.state.delay = Duration::from_millis(ms);
+        self.state.base_delay = self.state.delay;
+        self
+    }
+
+    // How steeply the score multiplier rises with speed, 0 turns it off
+    pub fn speed_bonus(mut self, slope: f64) -> Self {
+        self.state.speed_bonus = slope.max(0.0);
+        self
+    }
+
+    pub fn wrap(mut self, wrap: bool) -> Self {
+        self.state.edges = Edges::all(if wrap { Edge::Wrap } else { Edge::Wall });
+        self
+    }
+
+    // What each edge does when the snake runs off it
+    pub fn edges(mut self, edges: Edges) -> Self {
+        self.state.edges = edges;
+        self
+    }
+
+    pub fn snake_style(mut self, style: SnakeStyle) -> Self {
+        self.state.snake_style = style;
+        self
+    }
+
+    pub fn apple_style(mut self, style: AppleStyle) -> Self {
+        self.state.apple_style = style;
+        self
+    }
+
+    pub fn color(mut self, color: bool) -> Self {
+        self.state.color = color;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.state.seed = seed;
+        self
+    }
+
+    pub fn wrap_blocked(mut self, wrap_blocked: bool) -> Self {
+        self.state.wrap_blocked = wrap_blocked;
+        self
+    }
+
+    pub fn colorblind(mut self, colorblind: bool) -> Self {
+        self.state.colorblind = colorblind;
+        self
+    }
+
+    // Chance each tick of an apple magnet appearing, clamped to 0 to 1
+    pub fn magnet_rate(mut self, rate: f64) -> Self {
+        self.state.magnet_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn square(mut self, square: bool) -> Self {
+        self.state.square = square;
+        self
+    }
+
+    pub fn time_limit(mut self, limit: Option<Duration>) -> Self {
+        self.state.time_limit = limit;
+        self
+    }
+
+    pub fn compass(mut self, compass: bool) -> Self {
+        self.state.compass = compass;
+        self
+    }
+
+    pub fn apple_timeout(mut self, ticks: Option<u32>) -> Self {
+        self.state.apple_timeout = ticks;
+        self
+    }
+
+    // Show only what fits in a terminal this size, scrolling with the head
+    pub fn scroll(mut self, terminal: Option<(u16, u16)>) -> Self {
+        self.state.scroll = terminal;
+        self
+    }
+
+    // Close in the walls by a ring this often
+    pub fn shrink_every(mut self, every: Option<Duration>) -> Self {
+        self.state.shrink_every = every;
+        self
+    }
+
+    // Scatter this many random walls, placed from the seed
+    pub fn obstacles(mut self, count: usize) -> Self {
+        self.obstacles = count;
+        self
+    }
+
+    // How many cells to keep between random walls, and between them and the
+    // snake and apple
+    pub fn obstacle_gap(mut self, gap: u16) -> Self {
+        self.obstacle_gap = gap;
+        self
+    }
+
+    // Leave a dim afterimage where the tail has just been, when in color
+    pub fn blur(mut self, blur: bool) -> Self {
+        self.state.blur = blur;
+        self
+    }
+
+    // Pause by itself when no key has been pressed for this long
+    pub fn idle_pause(mut self, after: Option<Duration>) -> Self {
+        self.state.idle_pause = after;
+        self
+    }
+
+    // No wrapping and a fixed speed, for its own leaderboard, and none of
+    // the settings that make the game easier or play it for you
+    pub fn hardcore(mut self, hardcore: bool) -> Self {
+        self.state.hardcore = hardcore;
+        self
+    }
+
+    // Ticks to flash the head for after eating, 0 turns it off
+    pub fn eat_flash(mut self, ticks: u32) -> Self {
+        self.state.eat_flash = ticks;
+        self
+    }
+
+    // Where the first apple goes, instead of a random free cell, unless
+    // it's off the board or something's already there
+    pub fn apple(mut self, x: u16, y: u16) -> Self {
+        self.apple = Some((x, y));
+        self
+    }
+
+    // Chance of each new apple being a ghost, clamped to 0 to 1
+    pub fn ghost_rate(mut self, rate: f64) -> Self {
+        self.state.ghost_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    // Share the board with this many computer controlled snakes, outlasting
+    // them all wins
+    pub fn swarm(mut self, count: usize) -> Self {
+        self.swarm = count;
+        self
+    }
+
+    // Turn as soon as a key is pressed, instead of queueing turns for the
+    // start of each tick
+    pub fn immediate_turns(mut self, immediate: bool) -> Self {
+        self.state.immediate_turns = immediate;
+        self
+    }
+
+    // Show the snake's length next to its head
+    pub fn show_length(mut self, show: bool) -> Self {
+        self.state.show_length = show;
+        self
+    }
+
+    // Times dying goes back `depth` ticks instead of ending the game
+    pub fn rewinds(mut self, rewinds: u32, depth: usize) -> Self {
+        self.state.rewinds = rewinds;
+        self.state.rewind_depth = depth.max(1);
+        self
+    }
+
+    // Keep the snake this long, however much it eats, and eat as many apples
+    // as possible against the clock
+    pub fn time_attack(mut self, length: Option<usize>) -> Self {
+        self.state.fixed_length = length;
+        self
+    }
+
+    // Speed up by `boost` of the delay for each apple eaten in a row, wearing
+    // off by `decay` each tick, 0 boost turns streaks off
+    pub fn streak(mut self, boost: f64, decay: f64) -> Self {
+        self.state.streak_boost = boost.clamp(0.0, 1.0);
+        self.state.streak_decay = decay.clamp(0.0, 1.0);
+        self
+    }
+
+    // What shrinking the board does when it would cut into the snake
+    pub fn shrink_policy(mut self, policy: ShrinkPolicy) -> Self {
+        self.state.shrink_policy = policy;
+        self
+    }
+
+    // Running into a block that can't be pushed kills the snake, rather than
+    // stopping it
+    pub fn fatal_blocks(mut self, fatal: bool) -> Self {
+        self.state.fatal_blocks = fatal;
+        self
+    }
+
+    // Ticks the snake waits at a wall, for a turn to save it, before dying
+    pub fn wall_grace(mut self, ticks: u32) -> Self {
+        self.state.wall_grace = ticks;
+        self
+    }
+
+    // Leave a fading trail where the apple has been for this many ticks, 0
+    // turns it off
+    pub fn breadcrumbs(mut self, ticks: u32) -> Self {
+        self.state.breadcrumb_ticks = ticks;
+        self
+    }
+
+    // Arrow keys do the opposite of what they say, all game
+    pub fn reversed(mut self, reversed: bool) -> Self {
+        self.state.reversed = reversed;
+        self
+    }
+
+    // Chance each tick of a mirror appearing, which reverses the controls for
+    // a while, clamped to 0 to 1
+    pub fn mirror_rate(mut self, rate: f64) -> Self {
+        self.state.mirror_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    // Bonus points for the snake first growing to each length
+    pub fn milestones(mut self, milestones: Vec<(usize, u32)>) -> Self {
+        self.state.milestones = milestones;
+        self.state.milestones.sort_unstable();
+        self
+    }
+
+    // Only put apples in this rectangle, given as its top left cell and size
+    pub fn spawn_region(mut self, region: Option<Region>) -> Self {
+        self.state.spawn_region = region;
+        self
+    }
+
+    // Shake the screen for a moment after a close call
+    pub fn shake(mut self, shake: bool) -> Self {
+        self.state.shake = shake;
+        self
+    }
+
+    // Number the board's rows and columns along its top and left
+    pub fn rulers(mut self, rulers: bool) -> Self {
+        self.state.rulers = rulers;
+        self
+    }
+
+    // Flash the snake red a segment at a time when it dies, starting from
+    // `from` and waiting `ms` between segments
+    pub fn ripple(mut self, from: Option<Ripple>, ms: u64) -> Self {
+        self.state.ripple = from;
+        self.state.ripple_delay = Duration::from_millis(ms);
+        self
+    }
+
+    // Load position codes pasted into the game, in place of the board
+    pub fn accept_paste(mut self, on: bool) -> Self {
+        self.state.accept_paste = on;
+        self
+    }
+
+    // Let the snake steer itself towards the apples
+    pub fn autopilot(mut self, autopilot: bool) -> Self {
+        self.state.autopilot = autopilot;
+        self
+    }
+
+    // Queue at most `cap` turns, at least one, with `overflow` deciding which
+    // turn gives way once it's full
+    pub fn turn_queue(mut self, cap: usize, overflow: QueueOverflow) -> Self {
+        self.state.turn_queue = cap.max(1);
+        self.state.queue_overflow = overflow;
+        self
+    }
+
+    // Show where the snake's heading over this many ticks, and what it would
+    // crash into, 0 turns it off
+    pub fn lookahead(mut self, ticks: u32) -> Self {
+        self.state.lookahead = ticks;
+        self
+    }
+
+    // Keep apples more than this many cells from walls and walled edges,
+    // where there's room
+    pub fn apple_inset(mut self, cells: u16) -> Self {
+        self.state.apple_inset = cells;
+        self
+    }
+
+    // Lines of text above and below the game, `{seed}` and `{difficulty}`
+    // in them are filled in once the game's built
+    pub fn banners(mut self, title: &str, footer: &str) -> Self {
+        self.state.title = title.to_string();
+        self.state.footer = footer.to_string();
+        self
+    }
+
+    // Draw straight segments either side of a wrapping edge as arrows
+    pub fn wrap_arrows(mut self, arrows: bool) -> Self {
+        self.state.wrap_arrows = arrows;
+        self
+    }
+
+    // For this many ticks after eating, running into a snake passes over it
+    // and running into a wall waits there, 0 turns it off
+    pub fn shield(mut self, ticks: u32) -> Self {
+        self.state.shield_ticks = ticks;
+        self
+    }
+
+    // Which sides of the board to draw a border on
+    pub fn borders(mut self, borders: Borders) -> Self {
+        self.state.borders = borders;
+        self
+    }
+
+    // Letters to move with, instead of hjkl
+    pub fn keys(mut self, keys: Keys) -> Self {
+        self.state.keys = keys;
+        self
+    }
+
+    // Size the board to fill a terminal this size, and keep it filling the
+    // terminal as it's resized
+    pub fn fit_terminal(mut self, terminal: Option<(u16, u16)>) -> Self {
+        self.state.fit_terminal = terminal;
+        self
+    }
+
+    // Take `step_ms` off the delay every `every` apples, down to `floor_ms`,
+    // a step of 0 turns speeding up off
+    pub fn speed_up(mut self, step_ms: u64, every: u32, floor_ms: u64) -> Self {
+        self.state.speed_step = Duration::from_millis(step_ms);
+        self.state.speed_every = every.max(1);
+        self.state.speed_floor = Duration::from_millis(floor_ms);
+        self
+    }
+
+    // Play a campaign stage, counting from 0: its level, with more walls and
+    // a faster snake than the delay and obstacles set so far, cleared by
+    // eating `apples` apples
+    pub fn stage(mut self, stage: usize, apples: u32) -> Self {
+        let delay = u64::try_from(self.state.delay.as_millis()).unwrap_or(u64::MAX);
+        self.state.stage = Some(stage);
+        self.state.apple_goal = Some(apples.max(1));
+        self.obstacles += campaign::obstacles(stage);
+        self.level(campaign::level(stage))
+            .delay(campaign::delay(delay, stage))
+    }
+
+    // How well the swarm's snakes play
+    pub fn rival_skill(mut self, skill: RivalSkill) -> Self {
+        self.state.rival_skill = skill;
+        self
+    }
+
+    // Tron mode, the snake never shrinks and there are no apples, it scores
+    // a point for every tick it survives
+    pub fn tron(mut self, on: bool) -> Self {
+        self.state.tron = on;
+        self
+    }
+
+    // Today's daily challenge, which has a leaderboard of its own. The seed
+    // is set apart from this
+    pub fn daily(mut self, on: bool) -> Self {
+        self.state.daily = on;
+        self
+    }
+
+    // Attract mode, left alone for the idle pause the autopilot plays
+    // instead of the game pausing, until a key is pressed
+    pub fn attract(mut self, on: bool) -> Self {
+        self.state.attract = on;
+        self
+    }
+
+    // Where the game gets the time from, the real time unless it's set
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.state.clock = clock;
+        self
+    }
+
+    // Take the board, walls, snake and apple from a level
+    pub fn level(mut self, level: Level) -> Self {
+        self.apple = Some(level.apple);
+        self.state.load_level(level);
+        self
+    }
+
+    // Take the board, walls, snake and apple from a shared position
+    pub fn position(mut self, position: Position) -> Self {
+        self.apple = Some(position.apple);
+        self.state.load_position(position);
+        self
+    }
+
+    /// Check the game can be played, and return it
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`BuildError`] if the board is empty, the snake is off it,
+    /// there's nowhere to put a random apple, an edge wraps to a cell off the
+    /// board, or the apple spawn region is off the board or has no room in
+    /// it.
+    ///
+    /// Boards bigger than [`MAX_BOARD`] are cut down to it, along with any
+    /// walls past the new edges. An apple asked for off the board or on
+    /// something else goes on a random free cell instead.
+    pub fn build(self) -> Result<GameState, BuildError> {
+        let mut state = self.state;
+        fit_board(&mut state, GameState::resize_board);
+        state.board = (
+            state.board.0.min(MAX_BOARD.0),
+            state.board.1.min(MAX_BOARD.1),
+        );
+        let (width, height) = state.board;
+        let on_board = |(x, y): (u16, u16)| x < width && y < height;
+
+        if width == 0 || height == 0 {
+            return Err(BuildError::EmptyBoard);
+        }
+        state.walls.retain(|&cell| on_board(cell));
+        state.blocks.retain(|&cell| on_board(cell));
+        state.void.retain(|&cell| on_board(cell));
+        if !state.snake.iter().all(|s| on_board((s.0, s.1))) {
+            return Err(BuildError::SnakeOutOfBounds);
+        }
+
+        // Custom wrap targets must be on the edge they lead to
+        let Edges {
+            north,
+            south,
+            east,
+            west,
+        } = state.edges;
+        let fits = |edge: Edge, length: u16| !matches!(edge, Edge::WrapTo(to) if to >= length);
+        if !(fits(north, width) && fits(south, width) && fits(east, height) && fits(west, height)) {
+            return Err(BuildError::WrapTargetOutOfBounds);
+        }
+
+        // Apples have to be able to appear somewhere in the spawn region
+        if let Some(region) = state.spawn_region {
+            let fits = |start: u16, length: u16, limit: u16| {
+                length > 0 && start.checked_add(length).is_some_and(|end| end <= limit)
+            };
+            if !fits(region.origin.0, region.size.0, width)
+                || !fits(region.origin.1, region.size.1, height)
+            {
+                return Err(BuildError::SpawnRegionOutOfBounds);
+            }
+            let room = region.cells().any(|cell| {
+                !state.on_snake(cell)
+                    && !state.walls.contains(&cell)
+                    && !state.blocks.contains(&cell)
+            });
+            if !room {
+                return Err(BuildError::NoRoomForApple);
+            }
+        }
+
+        // Hardcore settings win over anything else asked for
+        if state.hardcore {
+            state.edges = Edges::all(Edge::Wall);
+            state.rewinds = 0;
+            state.wall_grace = 0;
+            state.shield_ticks = 0;
+            state.wrap_blocked = false;
+            state.delay = HARDCORE_DELAY;
+            state.base_delay = HARDCORE_DELAY;
+            state.accept_paste = false;
+            state.autopilot = false;
+            state.reversed = false;
+            state.magnet_rate = 0.0;
+            state.fixed_length = None;
+            state.attract = false;
+        }
+        let swarm = if state.hardcore { 0 } else { self.swarm };
+
+        let difficulty = if state.hardcore {
+            Mode::Hardcore
+        } else {
+            Mode::Casual
+        };
+        for banner in [&mut state.title, &mut state.footer] {
+            *banner = banner
+                .replace("{seed}", &state.seed.to_string())
+                .replace("{difficulty}", &difficulty.to_string());
+        }
+
+        if state.fixed_length.is_some() && state.time_limit.is_none() {
+            state.time_limit = Some(TIME_ATTACK_LIMIT);
+        }
+
+        let mut rng = StdRng::seed_from_u64(state.seed);
+        state.shrink_rng = StdRng::seed_from_u64(state.seed);
+        // The default apple isn't really there yet, so the head stands in
+        // for it, which is taken anyway. An apple asked for off the board or
+        // on something else goes on a random free cell, as if it hadn't been
+        state.apple = state.head;
+        state.apple = match self
+            .apple
+            .filter(|&cell| on_board(cell) && state.is_free(cell))
+        {
+            Some(cell) => cell,
+            None => state
+                .random_apple_cell(&mut rng)
+                .ok_or(BuildError::NoRoomForApple)?,
+        };
+        obstacles::generate(&mut state, self.obstacles, self.obstacle_gap, &mut rng);
+        state.spawn_rivals(swarm, &mut rng);
+        state.swarm = state.rivals.len();
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Nothing but the apple is on its cell
+    fn apple_alone(game: &GameState) -> bool {
+        let apple = game.apple;
+        on_board(game, apple)
+            && !game.on_snake(apple)
+            && !game.walls.contains(&apple)
+            && !game.blocks.contains(&apple)
+    }
+
+    fn on_board(game: &GameState, (x, y): (u16, u16)) -> bool {
+        x < game.board.0 && y < game.board.1
+    }
+
+    #[test]
+    fn hardcore_turns_off_the_extras() {
+        let game = GameStateBuilder::new()
+            .seed(1)
+            .hardcore(true)
+            .autopilot(true)
+            .reversed(true)
+            .swarm(2)
+            .magnet_rate(0.5)
+            .time_attack(Some(10))
+            .build()
+            .unwrap();
+        assert!(!game.autopilot);
+        assert!(!game.reversed);
+        assert!(game.rivals.is_empty());
+        assert!(game.magnet_rate == 0.0);
+        assert_eq!(game.fixed_length, None);
+        assert_eq!(game.time_limit, None);
+    }
+
+    #[test]
+    fn apple_goes_where_asked() {
+        let game = GameStateBuilder::new().seed(1).apple(7, 3).build().unwrap();
+        assert_eq!(game.apple, (7, 3));
+    }
+
+    #[test]
+    fn apple_on_the_snake_moves_off_it() {
+        let game = GameStateBuilder::new().seed(1).apple(1, 0).build().unwrap();
+        assert!(game.on_snake((1, 0)));
+        assert!(apple_alone(&game));
+    }
+
+    #[test]
+    fn random_apple_fits_a_tiny_board() {
+        for seed in 0..20 {
+            let game = GameStateBuilder::new()
+                .seed(seed)
+                .board(3, 3)
+                .build()
+                .unwrap();
+            assert!(apple_alone(&game));
+        }
+    }
+
+    #[test]
+    fn time_attack_lasts_a_minute_unless_told() {
+        let game = GameStateBuilder::new()
+            .seed(1)
+            .time_attack(Some(5))
+            .build()
+            .unwrap();
+        assert_eq!(game.time_limit, Some(TIME_ATTACK_LIMIT));
+
+        let game = GameStateBuilder::new()
+            .seed(1)
+            .time_limit(Some(Duration::from_secs(20)))
+            .time_attack(Some(5))
+            .build()
+            .unwrap();
+        assert_eq!(game.time_limit, Some(Duration::from_secs(20)));
+    }
+
+    #[test]
+    fn spawn_region_has_to_fit() {
+        let region = |origin, size| Some(Region { origin, size });
+        let build = |region| {
+            GameStateBuilder::new()
+                .seed(1)
+                .board(10, 10)
+                .spawn_region(region)
+                .build()
+                .err()
+        };
+        assert_eq!(
+            build(region((8, 8), (3, 1))),
+            Some(BuildError::SpawnRegionOutOfBounds)
+        );
+        assert_eq!(
+            build(region((0, 0), (0, 4))),
+            Some(BuildError::SpawnRegionOutOfBounds)
+        );
+        // Only room where the snake is
+        assert_eq!(
+            build(region((0, 0), (2, 1))),
+            Some(BuildError::NoRoomForApple)
+        );
+    }
+
+    #[test]
+    fn absurd_boards_are_cut_down() {
+        let mut builder = GameStateBuilder::new().seed(1).board(u16::MAX, u16::MAX);
+        builder.state.walls.insert((2000, 3));
+        let game = builder.build().unwrap();
+        assert_eq!(game.board, MAX_BOARD);
+        assert!(game.walls.is_empty());
+        assert!(apple_alone(&game));
+    }
+
+    #[test]
+    fn apple_off_the_board_moves_onto_it() {
+        let game = GameStateBuilder::new()
+            .seed(1)
+            .apple(40, 3)
+            .build()
+            .unwrap();
+        assert!(apple_alone(&game));
+    }
+}
@@ -0,0 +1,50 @@
+// Campaign mode, the bundled levels played one after another
+//
+// Each stage is the next level, with a few more random walls scattered over it
+// than the last and a faster snake. Eating enough apples clears a stage, and
+// the score carries on into the next one.
+
+use crate::level::Level;
+
+// The bundled levels, easiest first
+const LEVELS: [&str; 3] = [
+    include_str!("../levels/box.txt"),
+    include_str!("../levels/pillars.txt"),
+    include_str!("../levels/cross.txt"),
+];
+
+// Milliseconds taken off the delay each stage, down to MIN_DELAY
+const SPEED_STEP: u64 = 30;
+const MIN_DELAY: u64 = 80;
+
+// Random walls added each stage
+const OBSTACLE_STEP: usize = 3;
+
+/// How many stages there are
+#[must_use]
+pub fn stages() -> usize {
+    LEVELS.len()
+}
+
+/// The level for a stage, counting from 0
+///
+/// # Panics
+///
+/// Panics if `stage` is past the last stage, or its level doesn't parse.
+#[must_use]
+pub fn level(stage: usize) -> Level {
+    Level::parse(LEVELS[stage]).expect("bundled levels are valid")
+}
+
+/// The delay for a stage, in milliseconds, starting from `base`
+#[must_use]
+pub fn delay(base: u64, stage: usize) -> u64 {
+    let step = SPEED_STEP.saturating_mul(u64::try_from(stage).unwrap_or(u64::MAX));
+    base.saturating_sub(step).max(MIN_DELAY.min(base))
+}
+
+/// Random walls scattered over a stage on top of its level's own
+#[must_use]
+pub fn obstacles(stage: usize) -> usize {
+    OBSTACLE_STEP * stage
+}
@@ -0,0 +1,229 @@
+// Default settings read from a TOML file at startup
+//
+// Every setting can be left out, and anything given on the command line
+// wins over the file. With `remember = true` the settings a session ended
+// with, after any changes made with the keys, are written back when the
+// game closes, rewriting the whole file.
+
+use std::fmt::{self, Display};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::game::{AppleStyle, Edge, Edges, GameState, Keys, SnakeStyle};
+use crate::input::COMMAND_KEYS;
+
+/// Settings from the config file, None where the file leaves them out
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u16>,
+    /// Milliseconds between ticks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay: Option<u64>,
+    #[serde(
+        deserialize_with = "parsed",
+        serialize_with = "shown",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub snake_style: Option<SnakeStyle>,
+    #[serde(
+        deserialize_with = "parsed",
+        serialize_with = "shown",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub apple_style: Option<AppleStyle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wall_wrap: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<bool>,
+    /// Write the settings back to the file when the game closes
+    pub remember: bool,
+    pub keys: KeyBindings,
+}
+
+/// The `[keys]` table, letters to move with instead of `hjkl`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyBindings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub up: Option<char>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub down: Option<char>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub left: Option<char>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub right: Option<char>,
+}
+
+impl KeyBindings {
+    /// The movement keys, the usual ones where none is bound
+    #[must_use]
+    pub fn keys(&self) -> Keys {
+        let usual = Keys::default();
+        Keys {
+            up: self.up.unwrap_or(usual.up),
+            down: self.down.unwrap_or(usual.down),
+            left: self.left.unwrap_or(usual.left),
+            right: self.right.unwrap_or(usual.right),
+        }
+    }
+
+    // A bound letter can't take over a command, or move two ways
+    fn check(&self) -> Result<(), ConfigError> {
+        let bound = [self.up, self.down, self.left, self.right];
+        for (i, key) in bound.iter().enumerate() {
+            let Some(key) = *key else { continue };
+            if COMMAND_KEYS.contains(&key) {
+                return Err(ConfigError::KeyTaken(key));
+            }
+            if bound[i + 1..].contains(&Some(key)) {
+                return Err(ConfigError::KeyTwice(key));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    /// Take on the settings a game ended with. A board that came from a
+    /// level or position isn't kept, and neither are the speed and walls of
+    /// a hardcore game, which are fixed
+    pub fn update_from(&mut self, game: &GameState, board: bool) {
+        if board {
+            self.width = Some(game.board.0);
+            self.height = Some(game.board.1);
+        }
+        if !game.hardcore {
+            self.delay = u64::try_from(game.delay.as_millis()).ok();
+            self.wall_wrap = Some(game.edges == Edges::all(Edge::Wrap));
+        }
+        self.snake_style = Some(game.snake_style);
+        self.apple_style = Some(game.apple_style);
+        self.color = Some(game.color);
+    }
+}
+
+// Styles are written by name, as on the command line
+fn parsed<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = String>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|name| name.parse().map_err(de::Error::custom))
+        .transpose()
+}
+
+// Only called for styles that are set, the rest are skipped
+#[allow(clippy::ref_option)]
+fn shown<S, T>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Display,
+{
+    match value {
+        Some(value) => serializer.collect_str(value),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Write(toml::ser::Error),
+    KeyTaken(char),
+    KeyTwice(char),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "{e}"),
+            ConfigError::Parse(e) => write!(f, "{}", e.to_string().trim_end()),
+            ConfigError::Write(e) => write!(f, "{e}"),
+            ConfigError::KeyTaken(c) => write!(f, "[keys]: '{c}' is already a command key"),
+            ConfigError::KeyTwice(c) => write!(f, "[keys]: '{c}' is bound more than once"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// `config.toml` in the user's config directory, if there is one
+#[must_use]
+pub fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("snake-crossterm").join("config.toml"))
+}
+
+/// Read the config file at `path`, every setting left out if there isn't one
+///
+/// # Errors
+///
+/// Returns a [`ConfigError`] if the file can't be read or isn't a valid
+/// config, which includes binding a command key to movement.
+pub fn load(path: &Path) -> Result<Config, ConfigError> {
+    let config: Config = match fs::read_to_string(path) {
+        Ok(text) => toml::from_str(&text).map_err(ConfigError::Parse)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(ConfigError::Io(e)),
+    };
+    config.keys.check()?;
+    Ok(config)
+}
+
+/// Write a config to `path`, creating its directory if need be
+///
+/// # Errors
+///
+/// Returns a [`ConfigError`] if the file can't be written.
+pub fn save(path: &Path, config: &Config) -> Result<(), ConfigError> {
+    let text = toml::to_string(config).map_err(ConfigError::Write)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(ConfigError::Io)?;
+    }
+    fs::write(path, text).map_err(ConfigError::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<(), ConfigError> {
+        let config: Config = toml::from_str(text).map_err(ConfigError::Parse)?;
+        config.keys.check()
+    }
+
+    #[test]
+    fn movement_can_be_rebound() {
+        assert!(parse("[keys]\nup = \"i\"\nleft = \"j\"").is_ok());
+    }
+
+    #[test]
+    fn command_keys_cant_be_bound() {
+        assert!(matches!(
+            parse("[keys]\nleft = \"a\""),
+            Err(ConfigError::KeyTaken('a'))
+        ));
+        assert!(matches!(
+            parse("[keys]\nup = \"p\""),
+            Err(ConfigError::KeyTaken('p'))
+        ));
+    }
+
+    #[test]
+    fn one_key_cant_move_two_ways() {
+        assert!(matches!(
+            parse("[keys]\nup = \"w\"\ndown = \"w\""),
+            Err(ConfigError::KeyTwice('w'))
+        ));
+    }
+}
@@ -0,0 +1,50 @@
+// Daily challenge seeds
+//
+// The seed for a day is the 64-bit FNV-1a hash of its UTC date written as
+// YYYY-MM-DD, so everyone playing on the same day gets the same seed. This
+// mapping must not change, or old daily scores stop being comparable.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Today's UTC date as YYYY-MM-DD
+#[must_use]
+pub fn today() -> String {
+    date(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+    )
+}
+
+/// The UTC date of a time in seconds since the Unix epoch, as YYYY-MM-DD
+#[must_use]
+pub fn date(secs: u64) -> String {
+    let (year, month, day) = civil_from_days(secs / 86_400);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// The seed for a date written as YYYY-MM-DD
+#[must_use]
+pub fn seed_for(date: &str) -> u64 {
+    date.bytes().fold(FNV_OFFSET, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+// Year, month and day of a count of days since 1970-01-01
+// (Howard Hinnant's civil_from_days, for dates after the epoch)
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
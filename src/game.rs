@@ -0,0 +1,1648 @@
+// Game state and the rules for moving the snake
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crossterm::style::Color;
+use rand::prelude::*;
+
+use crate::builder::GameStateBuilder;
+use crate::clock::Clock;
+use crate::level::{Level, Zone};
+use crate::position::Position;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    North,
+    South,
+    East,
+    West,
+}
+
+impl Direction {
+    pub(crate) fn opposite(self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::South => Direction::North,
+            Direction::East => Direction::West,
+            Direction::West => Direction::East,
+        }
+    }
+}
+
+// A type of segment in the snake, for printing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SegmentType {
+    NorthSouth,
+    NorthEast,
+    NorthWest,
+    SouthEast,
+    SouthWest,
+    EastWest,
+}
+
+impl SegmentType {
+    pub(crate) fn from_next(a: Direction, b: Direction) -> SegmentType {
+        match a {
+            Direction::North => match b {
+                Direction::North => SegmentType::NorthSouth,
+                Direction::South => panic!(),
+                Direction::East => SegmentType::SouthEast,
+                Direction::West => SegmentType::SouthWest,
+            },
+            Direction::South => match b {
+                Direction::North => panic!(),
+                Direction::South => SegmentType::NorthSouth,
+                Direction::East => SegmentType::NorthEast,
+                Direction::West => SegmentType::NorthWest,
+            },
+            Direction::East => match b {
+                Direction::North => SegmentType::NorthWest,
+                Direction::South => SegmentType::SouthWest,
+                Direction::East => SegmentType::EastWest,
+                Direction::West => panic!(),
+            },
+            Direction::West => match b {
+                Direction::North => SegmentType::NorthEast,
+                Direction::South => SegmentType::SouthEast,
+                Direction::East => panic!(),
+                Direction::West => SegmentType::EastWest,
+            },
+        }
+    }
+
+    // Whether the segment connects to the cell east of it
+    pub(crate) fn joins_east(self) -> bool {
+        matches!(
+            self,
+            SegmentType::EastWest | SegmentType::NorthEast | SegmentType::SouthEast
+        )
+    }
+
+    pub(crate) fn from_dir(a: Direction) -> SegmentType {
+        match a {
+            Direction::North | Direction::South => SegmentType::NorthSouth,
+            Direction::East | Direction::West => SegmentType::EastWest,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SnakeStyle {
+    CurvedLine,
+    SharpLine,
+    Block,
+    Ascii,
+}
+
+impl SnakeStyle {
+    pub(crate) fn next(self) -> SnakeStyle {
+        match self {
+            SnakeStyle::CurvedLine => SnakeStyle::SharpLine,
+            SnakeStyle::SharpLine => SnakeStyle::Block,
+            SnakeStyle::Block => SnakeStyle::Ascii,
+            SnakeStyle::Ascii => SnakeStyle::CurvedLine,
+        }
+    }
+}
+
+impl fmt::Display for SnakeStyle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnakeStyle::CurvedLine => write!(f, "curved"),
+            SnakeStyle::SharpLine => write!(f, "sharp"),
+            SnakeStyle::Block => write!(f, "block"),
+            SnakeStyle::Ascii => write!(f, "ascii"),
+        }
+    }
+}
+
+impl FromStr for SnakeStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "curved" => Ok(SnakeStyle::CurvedLine),
+            "sharp" => Ok(SnakeStyle::SharpLine),
+            "block" => Ok(SnakeStyle::Block),
+            "ascii" => Ok(SnakeStyle::Ascii),
+            _ => Err(format!(
+                "unknown snake style '{s}', expected curved, sharp, block or ascii"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum AppleStyle {
+    Filled,
+    Outline,
+    Block,
+    Ascii,
+}
+
+impl AppleStyle {
+    pub(crate) fn next(self) -> AppleStyle {
+        match self {
+            AppleStyle::Filled => AppleStyle::Outline,
+            AppleStyle::Outline => AppleStyle::Block,
+            AppleStyle::Block => AppleStyle::Ascii,
+            AppleStyle::Ascii => AppleStyle::Filled,
+        }
+    }
+
+    pub(crate) fn display(self) -> char {
+        match self {
+            AppleStyle::Filled => '●',
+            AppleStyle::Outline => '○',
+            AppleStyle::Block => '█',
+            AppleStyle::Ascii => 'O',
+        }
+    }
+}
+
+impl fmt::Display for AppleStyle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AppleStyle::Filled => write!(f, "filled"),
+            AppleStyle::Outline => write!(f, "outline"),
+            AppleStyle::Block => write!(f, "block"),
+            AppleStyle::Ascii => write!(f, "ascii"),
+        }
+    }
+}
+
+impl FromStr for AppleStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "filled" => Ok(AppleStyle::Filled),
+            "outline" => Ok(AppleStyle::Outline),
+            "block" => Ok(AppleStyle::Block),
+            "ascii" => Ok(AppleStyle::Ascii),
+            _ => Err(format!(
+                "unknown apple style '{s}', expected filled, outline, block or ascii"
+            )),
+        }
+    }
+}
+
+impl SegmentType {
+    pub(crate) fn display(self, style: SnakeStyle) -> char {
+        match style {
+            SnakeStyle::CurvedLine => match self {
+                SegmentType::NorthSouth => '│',
+                SegmentType::NorthEast => '╰',
+                SegmentType::NorthWest => '╯',
+                SegmentType::SouthEast => '╭',
+                SegmentType::SouthWest => '╮',
+                SegmentType::EastWest => '─',
+            },
+            SnakeStyle::SharpLine => match self {
+                SegmentType::NorthSouth => '│',
+                SegmentType::NorthEast => '└',
+                SegmentType::NorthWest => '┘',
+                SegmentType::SouthEast => '┌',
+                SegmentType::SouthWest => '┐',
+                SegmentType::EastWest => '─',
+            },
+            SnakeStyle::Ascii => match self {
+                SegmentType::NorthSouth => '|',
+                SegmentType::NorthEast | SegmentType::NorthWest => '`',
+                SegmentType::SouthEast | SegmentType::SouthWest => '.',
+                SegmentType::EastWest => '-',
+            },
+            SnakeStyle::Block => '█', // All segments are blocks
+        }
+    }
+}
+
+// What happens when the snake runs off an edge of the board
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edge {
+    // A wall, the snake dies
+    Wall,
+    // The snake comes back in on the opposite edge, in line with where it left
+    Wrap,
+    // The snake comes back in on the opposite edge at this row, or column for
+    // the top and bottom edges
+    WrapTo(u16),
+}
+
+impl FromStr for Edge {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wall" => Ok(Edge::Wall),
+            "wrap" => Ok(Edge::Wrap),
+            _ => s.parse().map(Edge::WrapTo).map_err(|_| {
+                format!("unknown edge '{s}', expected wall, wrap or a row/column number")
+            }),
+        }
+    }
+}
+
+// What shrinking the board does when it would cut into the snake, apples or
+// walls
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShrinkPolicy {
+    // Shrink only as far as keeps everything on the board
+    Refuse,
+    // Shrink anyway, cutting off whatever ends up past the new edges
+    Clamp,
+    // Ask first, then shrink as with Clamp
+    Confirm,
+}
+
+impl FromStr for ShrinkPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "refuse" => Ok(ShrinkPolicy::Refuse),
+            "clamp" => Ok(ShrinkPolicy::Clamp),
+            "confirm" => Ok(ShrinkPolicy::Confirm),
+            _ => Err(format!(
+                "unknown shrink policy '{s}', expected refuse, clamp or confirm"
+            )),
+        }
+    }
+}
+
+// How well computer controlled snakes play, see swarm.rs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RivalSkill {
+    // Now and then wanders off the way to the apple
+    Easy,
+    // Always takes the shortest way to the nearest apple
+    Normal,
+    // Takes the shortest way, unless it leads somewhere too small to fit in
+    Hard,
+}
+
+impl FromStr for RivalSkill {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(RivalSkill::Easy),
+            "normal" => Ok(RivalSkill::Normal),
+            "hard" => Ok(RivalSkill::Hard),
+            _ => Err(format!(
+                "unknown rival skill '{s}', expected easy, normal or hard"
+            )),
+        }
+    }
+}
+
+// Where the head's heading, see `GameState::projection`
+pub(crate) struct Projection {
+    pub path: Vec<(u16, u16)>,
+    pub crash: Option<(u16, u16)>,
+}
+
+// Which turn gives way when the turn queue is full
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueueOverflow {
+    // The turn queued longest ago is forgotten to make room
+    DropOldest,
+    // The new turn is ignored
+    DropNewest,
+}
+
+impl FromStr for QueueOverflow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop-oldest" => Ok(QueueOverflow::DropOldest),
+            "drop-newest" => Ok(QueueOverflow::DropNewest),
+            _ => Err(format!(
+                "unknown overflow policy '{s}', expected drop-oldest or drop-newest"
+            )),
+        }
+    }
+}
+
+// Which end of the snake the death ripple starts from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ripple {
+    Head,
+    Tail,
+}
+
+impl FromStr for Ripple {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "head" => Ok(Ripple::Head),
+            "tail" => Ok(Ripple::Tail),
+            _ => Err(format!("unknown ripple '{s}', expected head or tail")),
+        }
+    }
+}
+
+// A rectangle of the board, from its top left cell
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Region {
+    pub origin: (u16, u16),
+    pub size: (u16, u16),
+}
+
+impl Region {
+    // Whether a cell is in the region
+    #[must_use]
+    pub fn contains(&self, (x, y): (u16, u16)) -> bool {
+        x >= self.origin.0
+            && y >= self.origin.1
+            && x - self.origin.0 < self.size.0
+            && y - self.origin.1 < self.size.1
+    }
+
+    // Every cell in the region
+    pub(crate) fn cells(self) -> impl Iterator<Item = (u16, u16)> {
+        let (left, top) = self.origin;
+        let (width, height) = self.size;
+        (left..left + width).flat_map(move |x| (top..top + height).map(move |y| (x, y)))
+    }
+}
+
+// The policy for each edge of the board
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Edges {
+    pub north: Edge,
+    pub south: Edge,
+    pub east: Edge,
+    pub west: Edge,
+}
+
+impl Edges {
+    #[must_use]
+    pub fn all(edge: Edge) -> Edges {
+        Edges {
+            north: edge,
+            south: edge,
+            east: edge,
+            west: edge,
+        }
+    }
+
+    // Whether the snake can leave by any edge
+    pub(crate) fn wraps(self) -> bool {
+        self != Edges::all(Edge::Wall)
+    }
+}
+
+/// Letters that move the snake, the arrow keys always do too
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keys {
+    pub up: char,
+    pub down: char,
+    pub left: char,
+    pub right: char,
+}
+
+impl Default for Keys {
+    fn default() -> Self {
+        Keys {
+            up: 'k',
+            down: 'j',
+            left: 'h',
+            right: 'l',
+        }
+    }
+}
+
+// Which sides of the board have a border drawn, only for looks, the edges
+// still do what they do
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Borders {
+    pub top: bool,
+    pub bottom: bool,
+    pub left: bool,
+    pub right: bool,
+}
+
+// Just the bottom and right, as the board has always been drawn, the top
+// and left of the terminal are the rest
+impl Default for Borders {
+    fn default() -> Self {
+        Borders {
+            top: false,
+            bottom: true,
+            left: false,
+            right: true,
+        }
+    }
+}
+
+impl FromStr for Borders {
+    type Err = String;
+
+    // "none", or the sides to draw separated by commas
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut borders = Borders {
+            top: false,
+            bottom: false,
+            left: false,
+            right: false,
+        };
+        if s.trim() == "none" {
+            return Ok(borders);
+        }
+        for side in s.split(',') {
+            match side.trim() {
+                "top" => borders.top = true,
+                "bottom" => borders.bottom = true,
+                "left" => borders.left = true,
+                "right" => borders.right = true,
+                _ => {
+                    return Err(format!(
+                        "unknown side '{side}', expected top, bottom, left or right"
+                    ))
+                }
+            }
+        }
+        Ok(borders)
+    }
+}
+
+// Points for an apple before the speed multiplier
+pub(crate) const APPLE_POINTS: u32 = 10;
+
+// Smallest the board can be resized to
+pub(crate) const MIN_BOARD: (u16, u16) = (2, 2);
+
+/// Biggest the board can be, however it's asked for. Far more than fits on
+/// a terminal, and far enough from `u16::MAX` that the sums drawing it
+/// (square cells, borders, rulers) can't overflow
+pub const MAX_BOARD: (u16, u16) = (1000, 1000);
+
+// Ticks the apple magnet lasts once collected
+pub(crate) const MAGNET_TICKS: u32 = 40;
+
+// Ticks the message for reaching a length milestone stays up
+pub(crate) const CHEER_TICKS: u32 = 12;
+
+// Ticks the message about a pasted position stays up
+pub(crate) const NOTICE_TICKS: u32 = 12;
+
+// Frames the screen shakes for after a close call
+pub(crate) const SHAKE_TICKS: u32 = 2;
+
+// Ticks the controls stay reversed once a mirror is collected
+pub(crate) const MIRROR_TICKS: u32 = 30;
+
+// How far (Manhattan) the apple jumps when eaten in teleport mode
+pub(crate) const TELEPORT_DISTANCE: u16 = 6;
+
+// Most a streak can shorten the tick delay by, as a fraction of it
+const MAX_BOOST: f64 = 0.75;
+
+// Shortest a streak can make the tick delay
+const MIN_BOOSTED_DELAY: Duration = Duration::from_millis(20);
+
+// How a game ended
+#[derive(Debug, Clone, Copy)]
+pub enum GameResult {
+    // The player pressed quit
+    Quit,
+    // The snake crashed while moving in this direction
+    Died(Direction),
+    // The time limit ran out
+    TimeUp,
+    // The player outlasted every rival snake
+    Won,
+    // The snake left nowhere free for another apple
+    Filled,
+    // Enough apples were eaten to clear a campaign stage
+    Cleared,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Segment(pub u16, pub u16, pub SegmentType, pub Direction);
+
+// A computer controlled snake, see swarm.rs
+#[derive(Debug, Clone)]
+pub struct Snake {
+    pub body: VecDeque<Segment>,
+    pub color: Color,
+}
+
+#[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct GameState {
+    pub snake: VecDeque<Segment>,
+    pub delay: Duration,
+    pub apple: (u16, u16),
+    pub head: (u16, u16),
+    pub board: (u16, u16),
+    pub direction: Direction,
+    pub snake_style: SnakeStyle,
+    pub apple_style: AppleStyle,
+    pub edges: Edges,
+    pub color: bool,
+    pub teleport: bool,
+    pub chase: u32,
+    pub score: u32,
+    pub apples: u32,
+    pub start: Instant,
+    pub magnet: Option<(u16, u16)>,
+    pub magnet_ticks: u32,
+    pub magnet_rate: f64,
+    pub square: bool,
+    pub time_limit: Option<Duration>,
+    pub compass: bool,
+    pub seed: u64,
+    pub apple_age: u32,
+    pub apple_timeout: Option<u32>,
+    pub quit: bool,
+    pub confirm_quit: bool,
+    pub walls: HashSet<(u16, u16)>,
+    pub wrap_blocked: bool,
+    pub colorblind: bool,
+    pub scroll: Option<(u16, u16)>,
+    pub shrink_every: Option<Duration>,
+    pub rings: u16,
+    pub base_delay: Duration,
+    pub speed_bonus: f64,
+    pub blur: bool,
+    pub afterimage: Option<Segment>,
+    pub idle_pause: Option<Duration>,
+    pub last_input: Instant,
+    pub idle: bool,
+    pub show_position: bool,
+    pub hardcore: bool,
+    pub eat_flash: u32,
+    pub flash: u32,
+    pub ghost_rate: f64,
+    pub ghost: Option<Direction>,
+    pub rivals: Vec<Snake>,
+    pub spare_apples: Vec<(u16, u16)>,
+    pub swarm: usize,
+    pub turns: VecDeque<Direction>,
+    pub immediate_turns: bool,
+    pub show_length: bool,
+    pub rewinds: u32,
+    pub rewind_depth: usize,
+    pub fixed_length: Option<usize>,
+    pub streak: u32,
+    pub boost: f64,
+    pub streak_boost: f64,
+    pub streak_decay: f64,
+    pub shrink_policy: ShrinkPolicy,
+    pub confirm_shrink: Option<(i16, i16)>,
+    pub shrink_rng: StdRng,
+    pub zones: Vec<Zone>,
+    pub blocks: HashSet<(u16, u16)>,
+    pub fatal_blocks: bool,
+    pub wall_grace: u32,
+    pub grace: Option<u32>,
+    pub void: HashSet<(u16, u16)>,
+    pub breadcrumb_ticks: u32,
+    pub breadcrumbs: Vec<((u16, u16), u32)>,
+    pub clock: Arc<dyn Clock>,
+    pub reversed: bool,
+    pub mirror: Option<(u16, u16)>,
+    pub mirror_ticks: u32,
+    pub mirror_rate: f64,
+    pub milestones: Vec<(usize, u32)>,
+    pub milestone: Option<(usize, u32)>,
+    pub cheer: u32,
+    pub spawn_region: Option<Region>,
+    pub shake: bool,
+    pub shaking: u32,
+    pub close_call: bool,
+    pub rulers: bool,
+    pub ripple: Option<Ripple>,
+    pub ripple_delay: Duration,
+    pub rippled: Option<usize>,
+    pub accept_paste: bool,
+    pub notice: Option<(&'static str, u32)>,
+    pub autopilot: bool,
+    pub turn_queue: usize,
+    pub queue_overflow: QueueOverflow,
+    pub lookahead: u32,
+    pub apple_inset: u16,
+    pub title: String,
+    pub footer: String,
+    pub wrap_arrows: bool,
+    pub shield_ticks: u32,
+    pub shield: u32,
+    pub borders: Borders,
+    pub paused: Option<Instant>,
+    pub keys: Keys,
+    pub fit_terminal: Option<(u16, u16)>,
+    pub speed_step: Duration,
+    pub speed_every: u32,
+    pub speed_floor: Duration,
+    pub stage: Option<usize>,
+    pub apple_goal: Option<u32>,
+    pub rival_skill: RivalSkill,
+    pub tron: bool,
+    pub daily: bool,
+    pub attract: bool,
+    pub attracting: bool,
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameState {
+    /// The default game, customised with [`GameStateBuilder`]
+    ///
+    /// Built the same way, so the first apple is on a free cell rather than
+    /// wherever the defaults left it.
+    ///
+    /// # Panics
+    ///
+    /// Never, the default game is always playable.
+    #[must_use]
+    pub fn new() -> Self {
+        GameStateBuilder::new()
+            .build()
+            .expect("the default game should be playable")
+    }
+
+    // Replace the board, walls, snake and apple with those of a level
+    pub fn load_level(&mut self, level: Level) {
+        let (x, y) = level.head;
+        self.snake.clear();
+        self.snake
+            .push_back(Segment(x - 1, y, SegmentType::EastWest, Direction::East));
+        self.snake
+            .push_back(Segment(x, y, SegmentType::EastWest, Direction::East));
+        self.head = level.head;
+        self.direction = Direction::East;
+        self.board = level.board;
+        self.walls = &level.walls | &level.void;
+        self.void = level.void;
+        self.apple = level.apple;
+        self.zones = level.zones;
+        self.blocks = level.blocks;
+    }
+
+    // Replace the board, walls, snake and apple with a shared position
+    pub fn load_position(&mut self, position: Position) {
+        let last = position.snake[position.snake.len() - 1];
+        self.head = (last.0, last.1);
+        self.direction = last.3;
+        self.snake = position.snake;
+        self.board = position.board;
+        self.walls = position.walls;
+        self.apple = position.apple;
+    }
+
+    // Take the next queued turn, at the start of each tick
+    // This is the one moment the direction for the tick is decided, so turns
+    // pressed during a tick always apply to the next one, one per tick in the
+    // order pressed. Turns that go nowhere new, straight on or back onto the
+    // neck, are skipped
+    // On autopilot the snake steers itself instead, whatever's been pressed
+    pub(crate) fn commit_turn(&mut self) {
+        let heading = self.snake[self.snake.len() - 1].3;
+        while let Some(turn) = self.turns.pop_front() {
+            if turn != heading && turn != heading.opposite() {
+                self.direction = turn;
+                break;
+            }
+        }
+        if self.autopilot {
+            self.steer();
+        }
+    }
+
+    // Smallest the board can be while keeping the snakes, apples and walls on it
+    fn content_size(&self) -> (u16, u16) {
+        let cells = self
+            .snake
+            .iter()
+            .map(|s| (s.0, s.1))
+            .chain(
+                self.rivals
+                    .iter()
+                    .flat_map(|r| r.body.iter().map(|s| (s.0, s.1))),
+            )
+            .chain([self.apple])
+            .chain(self.spare_apples.iter().copied())
+            .chain(self.walls.iter().copied())
+            .chain(self.blocks.iter().copied());
+        cells.fold(MIN_BOARD, |(w, h), (x, y)| (w.max(x + 1), h.max(y + 1)))
+    }
+
+    // The size growing or shrinking the board asks for, never below MIN_BOARD
+    // or above MAX_BOARD
+    fn resized(&self, dx: i16, dy: i16) -> (u16, u16) {
+        (
+            self.board
+                .0
+                .saturating_add_signed(dx)
+                .clamp(MIN_BOARD.0, MAX_BOARD.0),
+            self.board
+                .1
+                .saturating_add_signed(dy)
+                .clamp(MIN_BOARD.1, MAX_BOARD.1),
+        )
+    }
+
+    // Grow or shrink the board, never smaller than MIN_BOARD or than it takes
+    // to keep the snake, apple and walls on it
+    pub(crate) fn resize_board(&mut self, dx: i16, dy: i16) {
+        let (width, height) = self.resized(dx, dy);
+        let (min_x, min_y) = self.content_size();
+        self.board = (width.max(min_x), height.max(min_y));
+    }
+
+    // Whether resizing the board as asked would cut into anything on it
+    pub(crate) fn resize_cuts(&self, dx: i16, dy: i16) -> bool {
+        let (width, height) = self.resized(dx, dy);
+        let (min_x, min_y) = self.content_size();
+        width < min_x || height < min_y
+    }
+
+    // Shrink the board as asked, though never past the head and the segment
+    // behind it. Walls past the new edges go, apples move back onto the
+    // board, and snakes lose everything from their first segment off it,
+    // rivals left shorter than two segments go altogether
+    pub(crate) fn clamp_board(&mut self, dx: i16, dy: i16) {
+        let (width, height) = self
+            .snake
+            .iter()
+            .rev()
+            .take(2)
+            .fold(self.resized(dx, dy), |(w, h), s| {
+                (w.max(s.0 + 1), h.max(s.1 + 1))
+            });
+        self.board = (width, height);
+        let on_board = |(x, y): (u16, u16)| x < width && y < height;
+
+        self.walls.retain(|&cell| on_board(cell));
+        self.void.retain(|&cell| on_board(cell));
+        self.blocks.retain(|&cell| on_board(cell));
+        if self.magnet.is_some_and(|cell| !on_board(cell)) {
+            self.magnet = None;
+        }
+        if self.mirror.is_some_and(|cell| !on_board(cell)) {
+            self.mirror = None;
+        }
+
+        // Keep the part of a snake from its head back to the edge
+        let cut = |body: &mut VecDeque<Segment>| {
+            let keep = body
+                .iter()
+                .rev()
+                .take_while(|s| on_board((s.0, s.1)))
+                .count();
+            body.drain(..body.len() - keep);
+            if body.len() > 1 {
+                body[0].2 = SegmentType::from_dir(body[1].3);
+            }
+        };
+        cut(&mut self.snake);
+        for rival in &mut self.rivals {
+            cut(&mut rival.body);
+        }
+        self.rivals.retain(|rival| rival.body.len() > 1);
+
+        // Apples left off the board come back on somewhere free, or in the
+        // corner if there's nowhere. They're placed from the game's own
+        // generator, so a seeded game clamps the same way every time
+        let corner = (width - 1, height - 1);
+        let mut rng = self.shrink_rng.clone();
+        if !on_board(self.apple) {
+            self.apple = self.head;
+            self.apple = self.random_apple_cell(&mut rng).unwrap_or(corner);
+        }
+        for i in 0..self.spare_apples.len() {
+            if !on_board(self.spare_apples[i]) {
+                self.spare_apples[i] = self.random_apple_cell(&mut rng).unwrap_or(corner);
+            }
+        }
+        self.shrink_rng = rng;
+    }
+
+    // Time since the game started, by its clock, standing still while paused
+    pub(crate) fn elapsed(&self) -> Duration {
+        let now = self.paused.unwrap_or_else(|| self.clock.now());
+        now.saturating_duration_since(self.start)
+    }
+
+    // Time remaining in a timed game, None if there's no limit
+    pub(crate) fn time_left(&self) -> Option<Duration> {
+        self.time_limit
+            .map(|limit| limit.saturating_sub(self.elapsed()))
+    }
+
+    // Score multiplier for the current speed, how much faster the effective
+    // delay (after the speed keys and speed levels) is than the delay the
+    // game started with, scaled down by speed_bonus so it's gentle by default
+    pub(crate) fn speed_multiplier(&self) -> f64 {
+        let delay = self
+            .leveled_delay()
+            .max(Duration::from_millis(1))
+            .as_secs_f64();
+        let ratio = self.base_delay.as_secs_f64() / delay;
+        (1.0 + self.speed_bonus * (ratio - 1.0)).max(0.0)
+    }
+
+    // Speed levels gained so far, one every `speed_every` apples while
+    // speeding up is on
+    pub(crate) fn speed_level(&self) -> u32 {
+        if self.speed_step.is_zero() {
+            0
+        } else {
+            self.apples / self.speed_every
+        }
+    }
+
+    // The delay with a step taken off it for each speed level, though never
+    // below the floor, or longer than the speed keys have set
+    pub(crate) fn leveled_delay(&self) -> Duration {
+        let leveled = self
+            .delay
+            .saturating_sub(self.speed_step.saturating_mul(self.speed_level()));
+        leveled.max(self.speed_floor.min(self.delay))
+    }
+
+    // How long the next tick lasts, shortened by any speed levels and streak
+    // boost, though a boost never takes it below MIN_BOOSTED_DELAY
+    pub(crate) fn tick_delay(&self) -> Duration {
+        let delay = self.leveled_delay();
+        let boosted = delay.mul_f64(1.0 - self.boost);
+        boosted.max(MIN_BOOSTED_DELAY.min(delay))
+    }
+
+    // Eating keeps a streak going and adds to its boost, otherwise the boost
+    // wears off a little each tick, and the streak ends with it
+    pub(crate) fn update_streak(&mut self, ate: bool) {
+        if ate {
+            self.streak += 1;
+            self.boost = (self.boost + self.streak_boost).min(MAX_BOOST);
+        } else {
+            self.boost = (self.boost - self.streak_decay).max(0.0);
+            if self.boost <= 0.0 {
+                self.streak = 0;
+            }
+        }
+    }
+
+    // Points for eating an apple worth `points` at the current speed
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub(crate) fn apple_points(&self, points: u32) -> u32 {
+        (f64::from(points) * self.speed_multiplier()).round() as u32
+    }
+
+    // Pause, or carry on from a pause as if it never happened, so a timed
+    // game's clock doesn't run down while paused
+    pub(crate) fn toggle_pause(&mut self) {
+        let now = self.clock.now();
+        match self.paused.take() {
+            Some(since) => self.start += now.saturating_duration_since(since),
+            None => self.paused = Some(now),
+        }
+    }
+
+    // Whether nothing has been pressed for long enough to pause
+    // Timed games never pause, their clock keeps running
+    pub(crate) fn idle_too_long(&self) -> bool {
+        self.time_limit.is_none()
+            && self.idle_pause.is_some_and(|limit| {
+                self.clock.now().saturating_duration_since(self.last_input) >= limit
+            })
+    }
+
+    // Whether moving the head into a cell runs into the snake
+    // The tail moves out of the way, unless the snake won't shrink this tick:
+    // it's about to eat, is still growing out to a fixed length, or is a
+    // tron snake, which never shrinks
+    pub(crate) fn hits_snake(&self, cell: (u16, u16)) -> bool {
+        let eats =
+            cell == self.apple && !self.teleport && self.ghost.is_none_or(|d| d == self.direction)
+                || self.spare_apples.contains(&cell);
+        let grows = match self.fixed_length {
+            Some(length) => self.snake.len() < length,
+            None => eats,
+        };
+        let grows = grows || self.tron;
+        self.snake
+            .iter()
+            .skip(usize::from(!grows))
+            .any(|x| (x.0, x.1) == cell)
+    }
+
+    // Whether a cell is occupied by the snake
+    // Walls are checked separately, see `GameState::walls`
+    pub(crate) fn on_snake(&self, cell: (u16, u16)) -> bool {
+        self.snake.iter().any(|x| (x.0, x.1) == cell)
+    }
+
+    // Whether nothing is on a cell, no snake, wall, block, apple or power-up
+    pub(crate) fn is_free(&self, cell: (u16, u16)) -> bool {
+        !self.on_snake(cell)
+            && !self.on_rival(cell)
+            && !self.walls.contains(&cell)
+            && !self.blocks.contains(&cell)
+            && cell != self.apple
+            && !self.spare_apples.contains(&cell)
+            && Some(cell) != self.magnet
+            && Some(cell) != self.mirror
+    }
+
+    // Age the trail of where the apple has been by a tick, adding where it
+    // was at the start of the tick if it's moved since
+    pub(crate) fn update_breadcrumbs(&mut self, was: (u16, u16)) {
+        for (_, left) in &mut self.breadcrumbs {
+            *left -= 1;
+        }
+        self.breadcrumbs.retain(|&(_, left)| left > 0);
+        if self.breadcrumb_ticks > 0 && self.apple != was {
+            self.breadcrumbs.push((was, self.breadcrumb_ticks));
+        }
+    }
+
+    // Hold the snake still at a wall for the grace period, counting it down a
+    // tick, returning whether there was any of it left
+    pub(crate) fn hold_at_wall(&mut self) -> bool {
+        let left = self.grace.unwrap_or(self.wall_grace);
+        self.grace = left.checked_sub(1);
+        left > 0
+    }
+
+    // Push the block on a cell along by one, if where it would go is free,
+    // returning whether it moved
+    pub(crate) fn push_block(&mut self, cell: (u16, u16), direction: Direction) -> bool {
+        let Some((to, _)) = next_head(cell, self.board, direction, self.edges) else {
+            return false;
+        };
+        if !self.is_free(to) {
+            return false;
+        }
+        self.blocks.remove(&cell);
+        self.blocks.insert(to);
+        true
+    }
+
+    // A random free cell
+    pub(crate) fn random_free_cell(&self, rng: &mut impl Rng) -> Option<(u16, u16)> {
+        let free: Vec<(u16, u16)> = (0..self.board.0)
+            .flat_map(|x| (0..self.board.1).map(move |y| (x, y)))
+            .filter(|&cell| self.is_free(cell))
+            .collect();
+        free.choose(rng).copied()
+    }
+
+    // Whether a cell is somewhere apples can appear
+    fn in_spawn_region(&self, cell: (u16, u16)) -> bool {
+        self.spawn_region.is_none_or(|region| region.contains(cell))
+    }
+
+    // Whether a cell is more than `inset` cells from every wall and walled
+    // edge, edges that wrap don't count
+    fn clear_of_walls(&self, (x, y): (u16, u16), inset: u16) -> bool {
+        let (width, height) = self.board;
+        let edges = [
+            (self.edges.west, x),
+            (self.edges.east, width - 1 - x),
+            (self.edges.north, y),
+            (self.edges.south, height - 1 - y),
+        ];
+        if edges
+            .iter()
+            .any(|&(edge, gap)| edge == Edge::Wall && gap < inset)
+        {
+            return false;
+        }
+        let xs = x.saturating_sub(inset)..=x.saturating_add(inset);
+        !xs.into_iter().any(|wx| {
+            let ys = y.saturating_sub(inset)..=y.saturating_add(inset);
+            ys.into_iter().any(|wy| self.walls.contains(&(wx, wy)))
+        })
+    }
+
+    // A random free cell for an apple, in the spawn region and away from the
+    // walls while there's room, coming closer to the walls a cell at a time
+    // when there isn't, and then anywhere
+    pub(crate) fn random_apple_cell(&self, rng: &mut impl Rng) -> Option<(u16, u16)> {
+        let cells: Vec<(u16, u16)> = (0..self.board.0)
+            .flat_map(|x| (0..self.board.1).map(move |y| (x, y)))
+            .filter(|&cell| self.is_free(cell) && self.in_spawn_region(cell))
+            .collect();
+        (0..=self.apple_inset)
+            .rev()
+            .find_map(|inset| {
+                let clear: Vec<(u16, u16)> = cells
+                    .iter()
+                    .copied()
+                    .filter(|&cell| self.clear_of_walls(cell, inset))
+                    .collect();
+                clear.choose(rng).copied()
+            })
+            .or_else(|| self.random_free_cell(rng))
+    }
+
+    // Collect, run down and spawn the apple magnet, called once the head has moved
+    // Collecting it again while active restarts the timer rather than stacking
+    pub(crate) fn update_magnet(&mut self, rng: &mut impl Rng) {
+        if Some(self.head) == self.magnet {
+            self.magnet = None;
+            self.magnet_ticks = MAGNET_TICKS;
+        } else if self.magnet_ticks > 0 {
+            self.magnet_ticks -= 1;
+            self.pull_apple();
+        }
+
+        if self.magnet.is_none() && self.magnet_rate > 0.0 && rng.gen_bool(self.magnet_rate) {
+            self.magnet = self.random_free_cell(rng);
+        }
+    }
+
+    // Score the bonus for each length milestone the snake has grown past
+    // since last time, cheering the longest, or count the cheer down
+    pub(crate) fn reach_milestones(&mut self) {
+        let length = self.snake.len();
+        let reached = self.milestone.map_or(0, |(at, _)| at);
+        let mut cheer = None;
+        for &(at, bonus) in &self.milestones {
+            if at > reached && at <= length {
+                self.score += bonus;
+                cheer = Some((at, bonus));
+            }
+        }
+        if cheer.is_some() {
+            self.milestone = cheer;
+            self.cheer = CHEER_TICKS;
+        } else {
+            self.cheer = self.cheer.saturating_sub(1);
+        }
+    }
+
+    // Load a pasted position code in place of the board, if it's valid and
+    // the board's a size the game allows, and say how it went
+    pub(crate) fn load_pasted(&mut self, code: &str) {
+        let fits = |(width, height): (u16, u16)| {
+            (MIN_BOARD.0..=MAX_BOARD.0).contains(&width)
+                && (MIN_BOARD.1..=MAX_BOARD.1).contains(&height)
+        };
+        let Some(position) = Position::decode(code).ok().filter(|p| fits(p.board)) else {
+            self.notice = Some(("invalid code", NOTICE_TICKS));
+            return;
+        };
+        self.load_position(position);
+
+        // Whatever else was on the old board goes with it, apart from
+        // rivals with room on the new one
+        self.blocks.clear();
+        self.void.clear();
+        self.zones.clear();
+        self.spare_apples.clear();
+        self.magnet = None;
+        self.mirror = None;
+        self.breadcrumbs.clear();
+        self.afterimage = None;
+        self.turns.clear();
+        self.grace = None;
+        self.confirm_shrink = None;
+        let rivals = std::mem::take(&mut self.rivals);
+        self.rivals = rivals
+            .into_iter()
+            .filter(|rival| {
+                rival.body.iter().all(|s| {
+                    let cell = (s.0, s.1);
+                    cell.0 < self.board.0 && cell.1 < self.board.1 && self.is_free(cell)
+                })
+            })
+            .collect();
+        self.swarm = self.rivals.len();
+        self.notice = Some(("position loaded", NOTICE_TICKS));
+    }
+
+    // Count down the message about a pasted position
+    pub(crate) fn update_notice(&mut self) {
+        self.notice = self
+            .notice
+            .filter(|&(_, left)| left > 1)
+            .map(|(notice, left)| (notice, left - 1));
+    }
+
+    // Shake the screen when the head first comes alongside the snake's body,
+    // a wall or a walled edge, or count down a shake already going
+    pub(crate) fn update_shake(&mut self) {
+        let (x, y) = self.head;
+        let body = self.snake.len().saturating_sub(2);
+        let close = |cell: Option<(u16, u16)>, edge: Edge| match cell {
+            Some(cell) if cell.0 < self.board.0 && cell.1 < self.board.1 => {
+                self.walls.contains(&cell)
+                    || self.snake.iter().take(body).any(|s| (s.0, s.1) == cell)
+            }
+            _ => edge == Edge::Wall,
+        };
+        let close_call = close(y.checked_sub(1).map(|y| (x, y)), self.edges.north)
+            || close(Some((x, y + 1)), self.edges.south)
+            || close(Some((x + 1, y)), self.edges.east)
+            || close(x.checked_sub(1).map(|x| (x, y)), self.edges.west);
+
+        if self.shake && close_call && !self.close_call {
+            self.shaking = SHAKE_TICKS;
+        } else {
+            self.shaking = self.shaking.saturating_sub(1);
+        }
+        self.close_call = close_call;
+    }
+
+    // How far the frame is nudged right and down while shaking, never left
+    // or up so nothing goes off the terminal
+    pub(crate) fn shake_offset(&self) -> (u16, u16) {
+        match self.shaking {
+            0 => (0, 0),
+            n if n % 2 == 0 => (1, 0),
+            _ => (0, 1),
+        }
+    }
+
+    // Where the head goes over the next `lookahead` ticks if nothing changes,
+    // taking the queued turns in order, and the cell it would crash on if it
+    // crashes in that time (running off a walled edge just ends the path)
+    // The tail moves on a cell each tick, once the head has, so the snake's
+    // own cells free up a tick behind
+    pub(crate) fn projection(&self) -> Projection {
+        let mut path = Vec::new();
+        let mut head = self.head;
+        let mut heading = self.snake[self.snake.len() - 1].3;
+        let mut direction = self.direction;
+        let mut turns = self.turns.iter();
+        for tick in 0..self.lookahead as usize {
+            if let Some(&turn) = turns.find(|&&t| t != heading && t != heading.opposite()) {
+                direction = turn;
+            }
+            let Some((cell, _)) = next_head(head, self.board, direction, self.edges) else {
+                return Projection { path, crash: None };
+            };
+            let crashed = self.walls.contains(&cell)
+                || self.blocks.contains(&cell)
+                || self.on_rival(cell)
+                || self.snake.iter().skip(tick).any(|s| (s.0, s.1) == cell);
+            if crashed {
+                return Projection {
+                    path,
+                    crash: Some(cell),
+                };
+            }
+            path.push(cell);
+            head = cell;
+            heading = direction;
+        }
+        Projection { path, crash: None }
+    }
+
+    // Whether the segment this far from the tail has been reached by the
+    // death ripple
+    pub(crate) fn rippled_over(&self, index: usize) -> bool {
+        match (self.ripple, self.rippled) {
+            (Some(Ripple::Head), Some(lit)) => index + lit >= self.snake.len(),
+            (Some(Ripple::Tail), Some(lit)) => index < lit,
+            _ => false,
+        }
+    }
+
+    // Whether the arrow keys do the opposite of what they say, for good or
+    // for a while after collecting a mirror
+    pub(crate) fn controls_reversed(&self) -> bool {
+        self.reversed || self.mirror_ticks > 0
+    }
+
+    // Collect, run down and spawn the mirror that reverses the controls,
+    // called once the head has moved
+    // Collecting it again while active restarts the timer rather than stacking
+    pub(crate) fn update_mirror(&mut self, rng: &mut impl Rng) {
+        if Some(self.head) == self.mirror {
+            self.mirror = None;
+            self.mirror_ticks = MIRROR_TICKS;
+        } else if self.mirror_ticks > 0 {
+            self.mirror_ticks -= 1;
+        }
+
+        if self.mirror.is_none() && self.mirror_rate > 0.0 && rng.gen_bool(self.mirror_rate) {
+            self.mirror = self.random_free_cell(rng);
+        }
+    }
+
+    // Turn the outer ring of the play area into walls every shrink_every,
+    // moving the apple and magnet out of the way
+    // Returns true if the snake got caught by the walls or boxed in
+    pub(crate) fn shrink_walls(&mut self, rng: &mut impl Rng) -> bool {
+        let Some(every) = self.shrink_every else {
+            return false;
+        };
+        let due = self.elapsed().as_secs_f64() / every.as_secs_f64();
+        let (width, height) = self.board;
+
+        // Always leave at least one cell in the middle
+        while f64::from(self.rings) < due.floor()
+            && 2 * (self.rings + 1) < width
+            && 2 * (self.rings + 1) < height
+        {
+            let r = self.rings;
+            for x in r..width - r {
+                self.walls.insert((x, r));
+                self.walls.insert((x, height - 1 - r));
+            }
+            for y in r..height - r {
+                self.walls.insert((r, y));
+                self.walls.insert((width - 1 - r, y));
+            }
+            self.rings += 1;
+        }
+
+        let walls = &self.walls;
+        self.blocks.retain(|cell| !walls.contains(cell));
+        if self.magnet.is_some_and(|cell| self.walls.contains(&cell)) {
+            self.magnet = None;
+        }
+        if self.mirror.is_some_and(|cell| self.walls.contains(&cell)) {
+            self.mirror = None;
+        }
+        if self.walls.contains(&self.apple) {
+            if let Some(cell) = self.random_apple_cell(rng) {
+                self.apple = cell;
+            }
+        }
+
+        self.snake.iter().any(|s| self.walls.contains(&(s.0, s.1))) || self.boxed_in()
+    }
+
+    // Whether every move from the head runs into a wall, the snake or the edge
+    fn boxed_in(&self) -> bool {
+        [
+            Direction::North,
+            Direction::South,
+            Direction::East,
+            Direction::West,
+        ]
+        .into_iter()
+        .all(
+            |direction| match next_head(self.head, self.board, direction, self.edges) {
+                Some((cell, _)) => self.walls.contains(&cell) || self.hits_snake(cell),
+                None => true,
+            },
+        )
+    }
+
+    // Maybe make the new apple a ghost, only eaten moving one way
+    pub(crate) fn roll_ghost(&mut self, rng: &mut impl Rng) {
+        self.ghost = rng.gen_bool(self.ghost_rate).then(|| {
+            *[
+                Direction::North,
+                Direction::South,
+                Direction::East,
+                Direction::West,
+            ]
+            .choose(rng)
+            .unwrap()
+        });
+    }
+
+    // Count the ticks the apple has sat in one place, relocating it once
+    // apple_timeout is reached, given where it was before this tick
+    pub(crate) fn age_apple(&mut self, before: (u16, u16), rng: &mut impl Rng) {
+        if self.apple == before {
+            self.apple_age += 1;
+        } else {
+            self.apple_age = 0;
+        }
+
+        if let Some(timeout) = self.apple_timeout {
+            if self.apple_age >= timeout {
+                if let Some(cell) = self.random_apple_cell(rng) {
+                    self.apple = cell;
+                }
+                self.apple_age = 0;
+            }
+        }
+    }
+
+    // Move the apple one step towards the head, along the longer axis first,
+    // never onto the snake, a wall or the magnet
+    fn pull_apple(&mut self) {
+        let (ax, ay) = self.apple;
+        let (hx, hy) = self.head;
+        let step_x = (ax != hx).then(|| if hx > ax { (ax + 1, ay) } else { (ax - 1, ay) });
+        let step_y = (ay != hy).then(|| if hy > ay { (ax, ay + 1) } else { (ax, ay - 1) });
+        let steps = if ax.abs_diff(hx) >= ay.abs_diff(hy) {
+            [step_x, step_y]
+        } else {
+            [step_y, step_x]
+        };
+        if let Some(cell) = steps.into_iter().flatten().find(|&cell| {
+            !self.on_snake(cell) && !self.walls.contains(&cell) && Some(cell) != self.magnet
+        }) {
+            self.apple = cell;
+        }
+    }
+
+    // Move the apple to a free cell as close as possible to TELEPORT_DISTANCE
+    // (Manhattan) away from where it is now, so the constraint relaxes on
+    // cramped boards. Stays put if there are no free cells at all.
+    pub(crate) fn teleport_apple(&mut self, rng: &mut impl Rng) {
+        let distance = |(x, y): (u16, u16)| x.abs_diff(self.apple.0) + y.abs_diff(self.apple.1);
+        let free: Vec<(u16, u16)> = (0..self.board.0)
+            .flat_map(|x| (0..self.board.1).map(move |y| (x, y)))
+            .filter(|&cell| self.is_free(cell) && self.in_spawn_region(cell))
+            .collect();
+        let Some(best) = free
+            .iter()
+            .map(|&cell| distance(cell).abs_diff(TELEPORT_DISTANCE))
+            .min()
+        else {
+            return;
+        };
+        let candidates: Vec<(u16, u16)> = free
+            .into_iter()
+            .filter(|&cell| distance(cell).abs_diff(TELEPORT_DISTANCE) == best)
+            .collect();
+        self.apple = *candidates.choose(rng).unwrap();
+    }
+}
+
+// New head position, based on direction
+// Running off an edge does what that edge's policy says, None if it's a wall
+// Also returns whether the move wrapped around an edge
+pub(crate) fn next_head(
+    head: (u16, u16),
+    board: (u16, u16),
+    direction: Direction,
+    edges: Edges,
+) -> Option<((u16, u16), bool)> {
+    // Where along the opposite edge the snake comes back in, custom targets
+    // are kept on the board in case it's shrunk since
+    let along = |edge: Edge, from: u16, length: u16| match edge {
+        Edge::Wall => None,
+        Edge::Wrap => Some(from),
+        Edge::WrapTo(to) => Some(to.min(length - 1)),
+    };
+    match direction {
+        Direction::North => {
+            if head.1 > 0 {
+                Some(((head.0, head.1 - 1), false))
+            } else {
+                along(edges.north, head.0, board.0).map(|x| ((x, board.1 - 1), true))
+            }
+        }
+        Direction::South => {
+            if head.1 + 1 < board.1 {
+                Some(((head.0, head.1 + 1), false))
+            } else {
+                along(edges.south, head.0, board.0).map(|x| ((x, 0), true))
+            }
+        }
+        Direction::West => {
+            if head.0 > 0 {
+                Some(((head.0 - 1, head.1), false))
+            } else {
+                along(edges.west, head.1, board.1).map(|y| ((board.0 - 1, y), true))
+            }
+        }
+        Direction::East => {
+            if head.0 + 1 < board.0 {
+                Some(((head.0 + 1, head.1), false))
+            } else {
+                along(edges.east, head.1, board.1).map(|y| ((0, y), true))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::GameStateBuilder;
+    use rand::rngs::StdRng;
+
+    // A snake of four curled into a square, its head below its tail and
+    // heading north into it
+    fn curled() -> GameState {
+        let mut game = GameStateBuilder::new().seed(1).build().unwrap();
+        game.snake = VecDeque::from([
+            Segment(1, 1, SegmentType::EastWest, Direction::East),
+            Segment(2, 1, SegmentType::SouthWest, Direction::East),
+            Segment(2, 2, SegmentType::NorthWest, Direction::South),
+            Segment(1, 2, SegmentType::EastWest, Direction::West),
+        ]);
+        game.head = (1, 2);
+        game.direction = Direction::North;
+        game.apple = (5, 5);
+        game
+    }
+
+    #[test]
+    fn teleported_apple_keeps_off_walls_and_rivals() {
+        let mut game = curled();
+        game.apple = game.head;
+        game.walls = (0..game.board.0)
+            .flat_map(|x| (0..game.board.1).map(move |y| (x, y)))
+            .filter(|&cell| !game.on_snake(cell) && cell != (9, 9) && cell != (0, 9))
+            .collect();
+        game.rivals.push(Snake {
+            body: VecDeque::from([Segment(0, 9, SegmentType::EastWest, Direction::East)]),
+            color: Color::Cyan,
+        });
+        game.teleport_apple(&mut StdRng::seed_from_u64(1));
+        assert_eq!(game.apple, (9, 9));
+    }
+
+    #[test]
+    fn following_the_tail_is_safe() {
+        assert!(!curled().hits_snake((1, 1)));
+    }
+
+    #[test]
+    fn growing_into_the_tail_hits_it() {
+        let mut game = curled();
+        game.apple = (1, 1);
+        assert!(game.hits_snake((1, 1)));
+
+        // A teleporting apple doesn't grow the snake
+        game.teleport = true;
+        assert!(!game.hits_snake((1, 1)));
+    }
+
+    #[test]
+    fn grace_at_a_wall_runs_out() {
+        let mut game = curled();
+        assert!(!game.hold_at_wall());
+
+        game.wall_grace = 2;
+        game.grace = None;
+        let held: Vec<_> = (0..3).map(|_| game.hold_at_wall()).collect();
+        assert_eq!(held, [true, true, false]);
+
+        // Turning away in time starts the grace period afresh
+        game.grace = None;
+        assert!(game.hold_at_wall());
+    }
+
+    #[test]
+    fn mirrors_reverse_the_controls_for_a_while() {
+        let mut game = curled();
+        let mut rng = StdRng::seed_from_u64(1);
+        game.mirror = Some(game.head);
+        game.update_mirror(&mut rng);
+        assert!(game.controls_reversed());
+        assert_eq!(game.mirror, None);
+
+        // Another mirror restarts the timer rather than adding to it
+        game.update_mirror(&mut rng);
+        game.mirror = Some(game.head);
+        game.update_mirror(&mut rng);
+        assert_eq!(game.mirror_ticks, MIRROR_TICKS);
+
+        for _ in 0..MIRROR_TICKS {
+            game.update_mirror(&mut rng);
+        }
+        assert!(!game.controls_reversed());
+    }
+
+    #[test]
+    fn milestones_score_their_bonus_once() {
+        let mut game = GameStateBuilder::new()
+            .seed(1)
+            .milestones(vec![(3, 50), (5, 20)])
+            .build()
+            .unwrap();
+        let grow = |game: &mut GameState| {
+            game.snake.push_front(game.snake[0]);
+            game.reach_milestones();
+            game.score
+        };
+        game.reach_milestones();
+        assert_eq!(game.score, 0);
+        assert_eq!(grow(&mut game), 50);
+        assert_eq!(grow(&mut game), 50);
+        assert_eq!(grow(&mut game), 70);
+        assert_eq!(game.milestone, Some((5, 20)));
+
+        // Growing back past a milestone after losing length scores nothing
+        game.snake.truncate(2);
+        game.reach_milestones();
+        assert_eq!(grow(&mut game), 70);
+    }
+
+    // Where apples go over many random picks
+    fn apple_cells(game: &GameState) -> HashSet<(u16, u16)> {
+        let mut rng = StdRng::seed_from_u64(1);
+        (0..200)
+            .map(|_| game.random_apple_cell(&mut rng).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn apples_spawn_in_the_region() {
+        let region = Region {
+            origin: (3, 4),
+            size: (3, 2),
+        };
+        let game = GameStateBuilder::new()
+            .seed(1)
+            .spawn_region(Some(region))
+            .build()
+            .unwrap();
+        assert!(region.contains(game.apple));
+        // Anywhere in it but where the apple already is
+        let cells = apple_cells(&game);
+        assert!(cells.iter().all(|&cell| region.contains(cell)));
+        assert_eq!(cells.len(), 5);
+    }
+
+    fn inset_game(board: u16, inset: u16, wrap: bool) -> GameState {
+        GameStateBuilder::new()
+            .seed(1)
+            .board(board, board)
+            .wrap(wrap)
+            .apple_inset(inset)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn apples_keep_off_the_walls() {
+        let inside = |&(x, y): &(u16, u16)| (2..=4).contains(&x) && (2..=4).contains(&y);
+        let cells = apple_cells(&inset_game(7, 2, false));
+        assert!(cells.iter().all(inside));
+
+        // Edges that wrap aren't walls
+        let cells = apple_cells(&inset_game(7, 2, true));
+        assert!(!cells.iter().all(inside));
+    }
+
+    #[test]
+    fn apple_inset_gives_way_on_a_small_board() {
+        // Only the middle is a cell away from every wall
+        let game = inset_game(3, 3, false);
+        assert_eq!(game.apple, (1, 1));
+
+        // And with the apple there, anywhere free will do
+        assert_eq!(apple_cells(&game).len(), 6);
+    }
+
+    #[test]
+    fn resizing_stops_at_the_largest_board() {
+        let mut game = curled();
+        game.board = MAX_BOARD;
+        game.resize_board(i16::MAX, i16::MAX);
+        assert_eq!(game.board, MAX_BOARD);
+
+        // And shrinks no further than the apple at (5, 5)
+        game.resize_board(i16::MIN, i16::MIN);
+        assert_eq!(game.board, (6, 6));
+    }
+
+    // The snake's cells and the apple after clamping a game from `seed`
+    // down to 8 by 6, which leaves the apple off the board
+    fn clamped(seed: u64) -> (Vec<(u16, u16)>, (u16, u16)) {
+        let mut game = GameStateBuilder::new()
+            .seed(seed)
+            .board(20, 10)
+            .build()
+            .unwrap();
+        game.apple = (18, 8);
+        game.clamp_board(-12, -4);
+        let snake = game.snake.iter().map(|s| (s.0, s.1)).collect();
+        (snake, game.apple)
+    }
+
+    #[test]
+    fn same_seed_clamps_the_same() {
+        let (snake, apple) = clamped(5);
+        assert!(apple.0 < 8 && apple.1 < 6);
+        assert_eq!(clamped(5), (snake, apple));
+    }
+
+    #[test]
+    fn tron_snake_runs_into_its_tail() {
+        let mut game = curled();
+        game.tron = true;
+        assert!(game.hits_snake((1, 1)));
+
+        // Wherever the apple it can't see happens to be
+        game.apple = (1, 1);
+        assert!(game.hits_snake((1, 1)));
+    }
+
+    #[test]
+    fn growing_to_a_fixed_length_keeps_the_tail() {
+        let mut game = curled();
+        game.fixed_length = Some(6);
+        assert!(game.hits_snake((1, 1)));
+        game.fixed_length = Some(4);
+        assert!(!game.hits_snake((1, 1)));
+    }
+}
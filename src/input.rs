@@ -0,0 +1,636 @@
+// Keyboard and other terminal input
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{poll, read, Event, KeyCode, KeyEvent, KeyModifiers};
+
+use crate::game::{Direction, Edge, Edges, GameState, Keys, QueueOverflow, ShrinkPolicy};
+use crate::render::{fit_board, render_all};
+use crate::screen::Screen;
+
+/// Where the game gets its input events from, polled between ticks
+pub trait InputSource {
+    /// The next event, or None if there isn't one within `timeout`
+    ///
+    /// # Errors
+    ///
+    /// Any error ends input for the rest of the game.
+    fn poll(&mut self, timeout: Duration) -> io::Result<Option<Event>>;
+}
+
+/// Events from the terminal
+#[derive(Debug, Default)]
+pub struct CrosstermInput;
+
+impl InputSource for CrosstermInput {
+    fn poll(&mut self, timeout: Duration) -> io::Result<Option<Event>> {
+        if poll(timeout)? {
+            read().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+// Held down cycle and toggle keys repeat faster than this are ignored
+const THROTTLE: Duration = Duration::from_millis(150);
+
+// Keys that cycle styles or toggle settings, which shouldn't fire on every
+// key repeat
+const THROTTLED_KEYS: &[char] = &['7', '8', '9', '0', 'a', 'c', 'n', 'p', 't', 'x'];
+
+// Keys with a job of their own, which can't be bound to movement
+pub(crate) const COMMAND_KEYS: &[char] = &[
+    'q', 'p', '1', '2', '3', '4', '5', '6', '7', '8', '9', '0', '!', '@', '#', '$', 'a', 'c', 't',
+    'x', 'n',
+];
+
+// When each throttled key last did something
+#[derive(Debug, Default)]
+pub(crate) struct Throttle {
+    last: HashMap<char, Instant>,
+}
+
+impl Throttle {
+    // Whether a key should act now, rather than being swallowed as a repeat
+    fn allow(&mut self, key: char, now: Instant) -> bool {
+        match self.last.get(&key) {
+            Some(&last) if now.duration_since(last) < THROTTLE => false,
+            _ => {
+                self.last.insert(key, now);
+                true
+            }
+        }
+    }
+}
+
+// Cells to resize the board by when shift is held
+const BIG_RESIZE_STEP: i16 = 5;
+
+// Every event the terminal can send. Resizes always arrive, mouse events
+// only once mouse capture is enabled at startup, which the game doesn't do,
+// and pastes only once bracketed paste is, for pasting positions
+pub(crate) fn handle_input<O: Write>(
+    game: &mut GameState,
+    out: &mut Screen<O>,
+    throttle: &mut Throttle,
+    event: &Event,
+) -> io::Result<()> {
+    match event {
+        // Any key skips the rest of the death ripple
+        Event::Key(_) if game.rippled.is_some() => {
+            game.rippled = Some(game.snake.len());
+            Ok(())
+        }
+        Event::Key(k) => handle_key_event(game, out, throttle, k),
+        Event::Paste(text) if game.accept_paste => {
+            game.load_pasted(text);
+            out.invalidate();
+            render_all(out, game)
+        }
+        Event::Mouse(_) | Event::FocusGained | Event::FocusLost | Event::Paste(_) => Ok(()),
+
+        // Whatever was on the terminal may have moved, draw it all again,
+        // scrolling within the new size or fitting the board to it, a board
+        // too big for the terminal shrinks the same way as from the keys
+        Event::Resize(columns, rows) => {
+            let terminal = Some((*columns, *rows));
+            if game.scroll.is_some() {
+                game.scroll = terminal;
+            }
+            if game.fit_terminal.is_some() {
+                game.fit_terminal = terminal;
+                fit_board(game, shrink);
+            }
+            out.invalidate();
+            render_all(out, game)
+        }
+    }
+}
+
+fn handle_key_event<O: Write>(
+    game: &mut GameState,
+    out: &mut Screen<O>,
+    throttle: &mut Throttle,
+    k: &KeyEvent,
+) -> io::Result<()> {
+    // Any key wakes the game from an idle pause, and still does its job
+    game.last_input = game.clock.now();
+    if game.idle {
+        game.idle = false;
+        render_all(out, game)?;
+    }
+
+    // Raw mode turns Ctrl-C into a plain key event, so quit on it here
+    if k.code == KeyCode::Char('c') && k.modifiers.contains(KeyModifiers::CONTROL) {
+        game.attracting = false;
+        game.quit = true;
+        return Ok(());
+    }
+
+    // Any key but q ends an attract mode demo, for a game of their own
+    if game.attracting {
+        game.attracting = k.code != KeyCode::Char('q');
+        game.quit = true;
+        return Ok(());
+    }
+
+    // Answer to "Quit? (y/n)", y or Esc again quits, anything else carries on
+    if game.confirm_quit {
+        game.confirm_quit = false;
+        if matches!(k.code, KeyCode::Char('y') | KeyCode::Esc) {
+            game.quit = true;
+        }
+        return render_all(out, game);
+    }
+
+    // Answer to "Shrink into the snake? (y/n)", only y shrinks
+    if let Some((dx, dy)) = game.confirm_shrink.take() {
+        if k.code == KeyCode::Char('y') {
+            game.clamp_board(dx, dy);
+        }
+        return render_all(out, game);
+    }
+
+    // Letters bound to movement are never throttled
+    let code = bound(game.keys, k.code);
+    if let KeyCode::Char(c) = code {
+        if THROTTLED_KEYS.contains(&c) && !throttle.allow(c, game.clock.now()) {
+            return Ok(());
+        }
+    }
+
+    // Shift resizes the board in bigger steps, some terminals only send
+    // the shifted character (US layout) without the modifier
+    let shifted = k.modifiers.contains(KeyModifiers::SHIFT)
+        || matches!(k.code, KeyCode::Char('!' | '@' | '#' | '$'));
+    let step = if shifted { BIG_RESIZE_STEP } else { 1 };
+
+    if handle_key(game, code, step) {
+        render_all(out, game)?;
+    }
+    Ok(())
+}
+
+// Queue a turn for the start of the next tick, see `GameState::commit_turn`,
+// or make it straight away if turns aren't queued
+// Reversed controls swap the direction first, so turning back on the snake
+// is still caught against the way it's actually moving
+// The queue is capped, otherwise mashing keys queues up turns that play out
+// long after they were pressed, and a stale turn can end up taken after the
+// snake has already turned, sending it back onto itself
+fn turn(game: &mut GameState, direction: Direction) {
+    let direction = if game.controls_reversed() {
+        direction.opposite()
+    } else {
+        direction
+    };
+    if game.immediate_turns {
+        if game.snake[game.snake.len() - 1].3 != direction.opposite() {
+            game.direction = direction;
+        }
+    } else if game.turns.back() != Some(&direction) {
+        if game.turns.len() >= game.turn_queue {
+            match game.queue_overflow {
+                QueueOverflow::DropOldest => {
+                    game.turns.pop_front();
+                }
+                QueueOverflow::DropNewest => return,
+            }
+        }
+        game.turns.push_back(direction);
+    }
+}
+
+// Resize the board, following the shrink policy if shrinking would cut into
+// anything on it
+fn shrink(game: &mut GameState, dx: i16, dy: i16) {
+    if !game.resize_cuts(dx, dy) {
+        game.resize_board(dx, dy);
+        return;
+    }
+    match game.shrink_policy {
+        ShrinkPolicy::Refuse => game.resize_board(dx, dy),
+        ShrinkPolicy::Clamp => game.clamp_board(dx, dy),
+        ShrinkPolicy::Confirm => game.confirm_shrink = Some((dx, dy)),
+    }
+}
+
+// The letters bound to movement act as the arrow keys
+fn bound(keys: Keys, code: KeyCode) -> KeyCode {
+    match code {
+        KeyCode::Char(c) if c == keys.up => KeyCode::Up,
+        KeyCode::Char(c) if c == keys.down => KeyCode::Down,
+        KeyCode::Char(c) if c == keys.left => KeyCode::Left,
+        KeyCode::Char(c) if c == keys.right => KeyCode::Right,
+        code => code,
+    }
+}
+
+// Act on a key, after `bound`, returning whether the board needs redrawing
+fn handle_key(game: &mut GameState, code: KeyCode, step: i16) -> bool {
+    match code {
+        // Hardcore games keep their speed, walls and scoring
+        KeyCode::Char('5' | '6' | '9' | 't') if game.hardcore => false,
+
+        // Quit, picked up by the game loop on its next tick
+        KeyCode::Char('q') => {
+            game.quit = true;
+            false
+        }
+
+        // Pause, or carry on
+        KeyCode::Char('p') => {
+            game.toggle_pause();
+            true
+        }
+
+        // Ask before quitting, the snake waits for the answer
+        KeyCode::Esc => {
+            game.confirm_quit = true;
+            true
+        }
+
+        // Up
+        KeyCode::Up => {
+            turn(game, Direction::North);
+            false
+        }
+
+        // Down
+        KeyCode::Down => {
+            turn(game, Direction::South);
+            false
+        }
+
+        // Left
+        KeyCode::Left => {
+            turn(game, Direction::West);
+            false
+        }
+
+        // Right
+        KeyCode::Right => {
+            turn(game, Direction::East);
+            false
+        }
+
+        // Decrease board x
+        KeyCode::Char('1' | '!') => {
+            shrink(game, -step, 0);
+            true
+        }
+
+        // Increase board x
+        KeyCode::Char('2' | '@') => {
+            game.resize_board(step, 0);
+            true
+        }
+
+        // Decrease board y
+        KeyCode::Char('3' | '#') => {
+            shrink(game, 0, -step);
+            true
+        }
+
+        // Increase board y
+        KeyCode::Char('4' | '$') => {
+            game.resize_board(0, step);
+            true
+        }
+
+        // Decrease speed
+        KeyCode::Char('5') => {
+            game.delay = game.delay.checked_add(Duration::from_millis(20)).unwrap();
+            false
+        }
+
+        // Increase speed
+        KeyCode::Char('6') => {
+            game.delay = game.delay.saturating_sub(Duration::from_millis(20));
+            false
+        }
+
+        // Cycle snake style
+        KeyCode::Char('7') => {
+            game.snake_style = game.snake_style.next();
+            true
+        }
+
+        // Cycle apple style
+        KeyCode::Char('8') => {
+            game.apple_style = game.apple_style.next();
+            true
+        }
+
+        // Toggle wall wrapping (The snake lives on a torus !!)
+        // Any wrapping edge counts as on, and turning it back on wraps them all
+        KeyCode::Char('9') => {
+            game.edges = if game.edges.wraps() {
+                Edges::all(Edge::Wall)
+            } else {
+                Edges::all(Edge::Wrap)
+            };
+            false
+        }
+
+        // Toggle color
+        KeyCode::Char('0') => {
+            game.color = !game.color;
+            true
+        }
+
+        // Toggle square cells
+        KeyCode::Char('a') => {
+            game.square = !game.square;
+            true
+        }
+
+        // Toggle colorblind mode
+        KeyCode::Char('c') => {
+            game.colorblind = !game.colorblind;
+            true
+        }
+
+        // Toggle teleport mode (the apple runs away instead of growing the snake)
+        KeyCode::Char('t') => {
+            game.teleport = !game.teleport;
+            game.chase = 0;
+            false
+        }
+
+        // Show or hide the code for sharing this position
+        KeyCode::Char('x') => {
+            game.show_position = !game.show_position;
+            true
+        }
+
+        // Toggle the rulers along the top and left of the board
+        KeyCode::Char('n') => {
+            game.rulers = !game.rulers;
+            true
+        }
+
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::GameStateBuilder;
+    use crate::game::{Segment, SegmentType, NOTICE_TICKS};
+    use crate::position::Position;
+    use std::collections::{HashSet, VecDeque};
+
+    // Act on `c` the way a key press does, after any binding, at the
+    // smallest resize step
+    fn key(game: &mut GameState, c: char) {
+        let code = bound(game.keys, KeyCode::Char(c));
+        handle_key(game, code, 1);
+    }
+
+    // A game heading east with `keys` pressed during one tick
+    fn pressed(keys: &str) -> GameState {
+        let mut game = GameStateBuilder::new().seed(1).build().unwrap();
+        game.direction = Direction::East;
+        for c in keys.chars() {
+            key(&mut game, c);
+        }
+        game
+    }
+
+    #[test]
+    fn quick_turns_take_a_tick_each() {
+        let mut game = pressed("jh");
+        game.commit_turn();
+        assert_eq!(game.direction, Direction::South);
+
+        // Once the snake has gone down, the second turn applies
+        let last = game.snake.len() - 1;
+        game.snake[last].3 = Direction::South;
+        game.commit_turn();
+        assert_eq!(game.direction, Direction::West);
+    }
+
+    #[test]
+    fn turning_back_onto_the_neck_is_skipped() {
+        let mut game = pressed("hk");
+        game.commit_turn();
+        assert_eq!(game.direction, Direction::North);
+        assert!(game.turns.is_empty());
+    }
+
+    #[test]
+    fn immediate_turns_skip_the_queue() {
+        let mut game = GameStateBuilder::new()
+            .seed(1)
+            .immediate_turns(true)
+            .build()
+            .unwrap();
+        game.direction = Direction::East;
+        key(&mut game, 'j');
+        assert_eq!(game.direction, Direction::South);
+        assert!(game.turns.is_empty());
+    }
+
+    // Which way the snake heads next with reversed controls, after pressing
+    // a key while heading east
+    fn reversed_turn(c: char) -> Direction {
+        let mut game = GameStateBuilder::new()
+            .seed(1)
+            .reversed(true)
+            .build()
+            .unwrap();
+        game.direction = Direction::East;
+        key(&mut game, c);
+        game.commit_turn();
+        game.direction
+    }
+
+    #[test]
+    fn reversed_controls_turn_the_other_way() {
+        assert_eq!(reversed_turn('k'), Direction::South);
+        assert_eq!(reversed_turn('j'), Direction::North);
+        // Right is back onto the neck, which is still caught
+        assert_eq!(reversed_turn('l'), Direction::East);
+        assert_eq!(reversed_turn('h'), Direction::East);
+    }
+
+    fn paste(mut game: GameState, text: &str) -> GameState {
+        let mut screen = Screen::new(Vec::new());
+        let event = Event::Paste(text.to_string());
+        handle_input(&mut game, &mut screen, &mut Throttle::default(), &event).unwrap();
+        game
+    }
+
+    // The code for a game on a wide board, with a wall in it
+    fn shared() -> String {
+        let mut game = GameStateBuilder::new()
+            .seed(2)
+            .board(30, 8)
+            .build()
+            .unwrap();
+        game.walls.insert((20, 5));
+        Position::from_game(&game).encode().unwrap()
+    }
+
+    #[test]
+    fn pasted_positions_load() {
+        let mut game = GameStateBuilder::new()
+            .seed(1)
+            .accept_paste(true)
+            .build()
+            .unwrap();
+        game.blocks.insert((3, 3));
+        let game = paste(game, &format!("  {}\n", shared()));
+        assert_eq!(game.board, (30, 8));
+        assert_eq!(game.walls, HashSet::from([(20, 5)]));
+        assert!(game.blocks.is_empty());
+        assert_eq!(game.notice, Some(("position loaded", NOTICE_TICKS)));
+    }
+
+    #[test]
+    fn invalid_pastes_leave_the_game_alone() {
+        let game = GameStateBuilder::new()
+            .seed(1)
+            .accept_paste(true)
+            .build()
+            .unwrap();
+        let board = game.board;
+        let game = paste(game, "hello there");
+        assert_eq!(game.board, board);
+        assert_eq!(game.notice, Some(("invalid code", NOTICE_TICKS)));
+    }
+
+    #[test]
+    fn pastes_are_ignored_unless_accepted() {
+        let game = GameStateBuilder::new().seed(1).build().unwrap();
+        let board = game.board;
+        let game = paste(game, &shared());
+        assert_eq!(game.board, board);
+        assert_eq!(game.notice, None);
+    }
+
+    // The turns queued after mashing up, right, down and left
+    fn mashed(overflow: QueueOverflow) -> Vec<Direction> {
+        let mut game = GameStateBuilder::new()
+            .seed(1)
+            .turn_queue(2, overflow)
+            .build()
+            .unwrap();
+        for c in ['k', 'l', 'j', 'h'] {
+            key(&mut game, c);
+            assert!(game.turns.len() <= 2);
+        }
+        game.turns.into_iter().collect()
+    }
+
+    #[test]
+    fn full_turn_queue_drops_the_newest() {
+        let turns = mashed(QueueOverflow::DropNewest);
+        assert_eq!(turns, [Direction::North, Direction::East]);
+    }
+
+    #[test]
+    fn full_turn_queue_can_drop_the_oldest() {
+        let turns = mashed(QueueOverflow::DropOldest);
+        assert_eq!(turns, [Direction::South, Direction::West]);
+    }
+
+    // A game fitted to a wide terminal, with a long snake along the top
+    // heading back to the left edge
+    fn fitted(policy: ShrinkPolicy) -> GameState {
+        let mut game = GameStateBuilder::new()
+            .seed(1)
+            .shrink_policy(policy)
+            .fit_terminal(Some((60, 20)))
+            .build()
+            .unwrap();
+        game.snake = (0..20)
+            .rev()
+            .map(|x| Segment(x, 2, SegmentType::EastWest, Direction::West))
+            .collect::<VecDeque<_>>();
+        game.head = (0, 2);
+        game.direction = Direction::West;
+        game.apple = (0, 0);
+        game.walls.clear();
+        game
+    }
+
+    fn press(game: &mut GameState, throttle: &mut Throttle, c: char) {
+        let mut screen = Screen::new(Vec::new());
+        let event = Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        handle_input(game, &mut screen, throttle, &event).unwrap();
+    }
+
+    #[test]
+    fn held_toggles_only_fire_once() {
+        let mut game = GameStateBuilder::new().seed(1).build().unwrap();
+        let mut throttle = Throttle::default();
+        let rulers = game.rulers;
+        press(&mut game, &mut throttle, 'n');
+        press(&mut game, &mut throttle, 'n');
+        assert_eq!(game.rulers, !rulers);
+    }
+
+    #[test]
+    fn movement_letters_are_never_throttled() {
+        let mut game = GameStateBuilder::new().seed(1).build().unwrap();
+        // Letters the throttle would otherwise swallow when held
+        game.keys.up = 't';
+        game.keys.right = 'x';
+        game.direction = Direction::East;
+        game.turn_queue = 3;
+        let mut throttle = Throttle::default();
+        for c in ['t', 'x', 't'] {
+            press(&mut game, &mut throttle, c);
+        }
+        let turns: Vec<_> = game.turns.iter().copied().collect();
+        assert_eq!(turns, [Direction::North, Direction::East, Direction::North]);
+    }
+
+    fn resize(game: &mut GameState, columns: u16, rows: u16) {
+        let mut screen = Screen::new(Vec::new());
+        let event = Event::Resize(columns, rows);
+        handle_input(game, &mut screen, &mut Throttle::default(), &event).unwrap();
+    }
+
+    #[test]
+    fn narrow_terminal_refuses_to_cut_the_snake() {
+        let mut game = fitted(ShrinkPolicy::Refuse);
+        resize(&mut game, 12, 20);
+        assert_eq!(game.board.0, 20);
+        assert_eq!(game.snake.len(), 20);
+    }
+
+    #[test]
+    fn narrow_terminal_clamps_the_snake() {
+        let mut game = fitted(ShrinkPolicy::Clamp);
+        resize(&mut game, 12, 20);
+        assert!(game.board.0 < 20);
+        assert!(game.snake.iter().all(|s| s.0 < game.board.0));
+        assert_eq!(game.head, (0, 2));
+    }
+
+    #[test]
+    fn narrow_terminal_asks_before_cutting() {
+        let mut game = fitted(ShrinkPolicy::Confirm);
+        let board = game.board;
+        resize(&mut game, 12, 20);
+        assert_eq!(game.board, board);
+        let Some((dx, 0)) = game.confirm_shrink else {
+            panic!("no shrink to confirm");
+        };
+        assert!(dx < 0);
+
+        let mut screen = Screen::new(Vec::new());
+        let yes = Event::Key(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE));
+        handle_input(&mut game, &mut screen, &mut Throttle::default(), &yes).unwrap();
+        assert!(game.board.0 < 20);
+        assert!(game.snake.iter().all(|s| s.0 < game.board.0));
+        assert!(game.confirm_shrink.is_none());
+    }
+}
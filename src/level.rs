@@ -0,0 +1,180 @@
+// Plain-text level layouts
+//
+// Each line of the file is a row of the board, each character a cell:
+//
+//   #  wall
+//   @  snake head, the tail starts on the cell to its left, heading east
+//   *  apple
+//   o  block, which the snake can push along
+//   x  outside the board, for shapes other than rectangles
+//   .  empty (a space works too)
+//
+// The board is as wide as the longest line, shorter lines are padded with
+// empty cells. Cells outside the board are walls, only the ones next to the
+// board are drawn, outlining it.
+//
+// Lines starting with `zone` aren't part of the layout, each tints the
+// background of the empty cells in a rectangle, purely for looks:
+//
+//   zone <x> <y> <width> <height> <color>
+//
+// Colors are crossterm's names, like `dark_blue` or `grey`. Later zones are
+// drawn over earlier ones, and anything off the board is left out.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crossterm::style::Color;
+
+#[derive(Debug, Clone)]
+pub struct Level {
+    pub board: (u16, u16),
+    pub walls: HashSet<(u16, u16)>,
+    pub head: (u16, u16),
+    pub apple: (u16, u16),
+    pub blocks: HashSet<(u16, u16)>,
+    pub void: HashSet<(u16, u16)>,
+    pub zones: Vec<Zone>,
+}
+
+/// A rectangle of the board with its empty cells tinted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Zone {
+    pub origin: (u16, u16),
+    pub size: (u16, u16),
+    pub color: Color,
+}
+
+impl Zone {
+    // Read a `zone` line, None if it isn't one
+    fn parse(line: &str) -> Option<Zone> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let ["zone", x, y, width, height, color] = fields[..] else {
+            return None;
+        };
+        Some(Zone {
+            origin: (x.parse().ok()?, y.parse().ok()?),
+            size: (width.parse().ok()?, height.parse().ok()?),
+            color: Color::try_from(color).ok()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LevelError {
+    Empty,
+    TooLarge,
+    UnexpectedChar(char, u16, u16),
+    NoSnake,
+    MultipleSnakes,
+    NoApple,
+    MultipleApples,
+    NoRoomForTail(u16, u16),
+    BadZone(usize),
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelError::Empty => write!(f, "level is empty"),
+            LevelError::TooLarge => write!(f, "level is too large"),
+            LevelError::UnexpectedChar(c, x, y) => {
+                write!(f, "unexpected character '{c}' at column {x}, row {y}")
+            }
+            LevelError::NoSnake => write!(f, "no snake start ('@')"),
+            LevelError::MultipleSnakes => write!(f, "more than one snake start ('@')"),
+            LevelError::NoApple => write!(f, "no apple ('*')"),
+            LevelError::MultipleApples => write!(f, "more than one apple ('*')"),
+            LevelError::NoRoomForTail(x, y) => write!(
+                f,
+                "snake start at column {x}, row {y} needs an empty cell to its left for the tail"
+            ),
+            LevelError::BadZone(line) => write!(
+                f,
+                "line {line} should be 'zone <x> <y> <width> <height> <color>'"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+impl Level {
+    /// Parse a level from its text layout
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`LevelError`] describing the first problem found.
+    pub fn parse(text: &str) -> Result<Level, LevelError> {
+        let mut rows = Vec::new();
+        let mut zones = Vec::new();
+        for (n, line) in (1..).zip(text.lines()) {
+            if line.starts_with("zone") {
+                zones.push(Zone::parse(line).ok_or(LevelError::BadZone(n))?);
+            } else {
+                rows.push(line);
+            }
+        }
+        let height = u16::try_from(rows.len()).map_err(|_| LevelError::TooLarge)?;
+        let width = rows
+            .iter()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0);
+        let width = u16::try_from(width).map_err(|_| LevelError::TooLarge)?;
+        if width == 0 || height == 0 {
+            return Err(LevelError::Empty);
+        }
+
+        let mut walls = HashSet::new();
+        let mut blocks = HashSet::new();
+        let mut void = HashSet::new();
+        let mut head = None;
+        let mut apple = None;
+        for (y, row) in (0..).zip(&rows) {
+            for (x, c) in (0..).zip(row.chars()) {
+                match c {
+                    '#' => {
+                        walls.insert((x, y));
+                    }
+                    'o' => {
+                        blocks.insert((x, y));
+                    }
+                    'x' => {
+                        void.insert((x, y));
+                    }
+                    '@' if head.is_some() => return Err(LevelError::MultipleSnakes),
+                    '@' => head = Some((x, y)),
+                    '*' if apple.is_some() => return Err(LevelError::MultipleApples),
+                    '*' => apple = Some((x, y)),
+                    '.' | ' ' => {}
+                    _ => return Err(LevelError::UnexpectedChar(c, x, y)),
+                }
+            }
+        }
+
+        let head = head.ok_or(LevelError::NoSnake)?;
+        let apple = apple.ok_or(LevelError::NoApple)?;
+
+        // The tail goes to the left of the head
+        let tail = (head.0.wrapping_sub(1), head.1);
+        if head.0 == 0
+            || walls.contains(&tail)
+            || blocks.contains(&tail)
+            || void.contains(&tail)
+            || apple == tail
+        {
+            return Err(LevelError::NoRoomForTail(head.0, head.1));
+        }
+
+        Ok(Level {
+            board: (width, height),
+            walls,
+            head,
+            apple,
+            blocks,
+            void,
+            zones,
+        })
+    }
+}
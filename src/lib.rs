@@ -0,0 +1,911 @@
+#![deny(clippy::all, clippy::pedantic)]
+
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::prelude::*;
+
+pub mod builder;
+pub mod campaign;
+pub mod clock;
+pub mod config;
+pub mod daily;
+pub mod game;
+pub mod input;
+pub mod level;
+pub mod observer;
+mod obstacles;
+pub mod position;
+mod render;
+pub mod replay;
+mod screen;
+pub mod stats;
+mod swarm;
+
+use game::{next_head, Direction, GameResult, GameState, Segment, SegmentType, APPLE_POINTS};
+use input::{handle_input, InputSource, Throttle};
+use observer::GameObserver;
+use render::{board_filled, cleared, game_over, quit, render_all, time_up, you_win};
+use screen::Screen;
+
+// Longest the death ripple takes to run down the snake
+const MAX_RIPPLE: Duration = Duration::from_secs(2);
+
+// The keyboard and screen a game is played on, both only used from the
+// game loop, which handles input while it waits between ticks
+struct Console<I, O: Write> {
+    // None once input has ended, the game carries on without it
+    input: Option<I>,
+    screen: Screen<O>,
+    throttle: Throttle,
+    // Set once drawing fails, which ends the game as if it was quit
+    failed: bool,
+}
+
+impl<I: InputSource, O: Write> Console<I, O> {
+    fn render(&mut self, game: &GameState) {
+        self.failed |= render_all(&mut self.screen, game).is_err();
+    }
+
+    // Wait out `delay` by the game's clock, handling events as they come in
+    // Whatever's left once input goes quiet is slept through, which for a
+    // clock that only moves when told to is all of it
+    fn wait(&mut self, game: &mut GameState, delay: Duration) {
+        let clock = Arc::clone(&game.clock);
+        let deadline = clock.now() + delay;
+        while let Some(input) = &mut self.input {
+            let left = deadline.saturating_duration_since(clock.now());
+            match input.poll(left) {
+                Ok(Some(event)) => {
+                    let handled = handle_input(game, &mut self.screen, &mut self.throttle, &event);
+                    self.failed |= handled.is_err();
+                }
+                Ok(None) => break,
+                Err(_) => self.input = None,
+            }
+        }
+        clock.sleep(deadline.saturating_duration_since(clock.now()));
+    }
+}
+
+// Whether the game is paused for lack of input, pausing it if it's been
+// left alone for too long, or in attract mode handing it to the autopilot
+fn idle<I: InputSource, O: Write>(game: &mut GameState, console: &mut Console<I, O>) -> bool {
+    if game.attract && !game.attracting && game.idle_too_long() {
+        game.attracting = true;
+        game.autopilot = true;
+    } else if !game.attract && !game.idle && game.idle_too_long() {
+        game.idle = true;
+        console.render(game);
+    }
+    game.idle
+}
+
+// Keep the snake where it is for this tick
+fn hold<I: InputSource, O: Write>(game: &mut GameState, console: &mut Console<I, O>) {
+    console.render(game);
+    let delay = game.tick_delay();
+    console.wait(game, delay);
+}
+
+// Move the snake on to `new_head`, going `direction`, growing it and moving
+// the apple if it's there, returning whether there was nowhere free to put
+// the next apple
+fn advance(
+    game: &mut GameState,
+    new_head: (u16, u16),
+    direction: Direction,
+    rng: &mut impl Rng,
+) -> bool {
+    // Set head
+    game.head = new_head;
+
+    // Update second-to-last segment
+    // Uses the direction the head actually moved in
+    let len = game.snake.len();
+    game.snake[len - 1].2 = SegmentType::from_next(game.snake[len - 1].3, direction);
+
+    // New head segment
+    let segment = Segment(
+        new_head.0,
+        new_head.1,
+        SegmentType::from_dir(direction),
+        direction,
+    );
+
+    // Add new head segment
+    game.snake.push_back(segment);
+    let apple = game.apple;
+
+    let full = eat(game, apple, direction, rng);
+
+    // Straighten the tail, it no longer joins the popped segment
+    let next = game.snake[1].3;
+    game.snake[0].2 = SegmentType::from_dir(next);
+
+    full
+}
+
+// Grow or score from whatever apple the head has just moved onto, `apple` is
+// where the apple was before the move, returning whether there was nowhere
+// free to put the next apple
+fn eat(game: &mut GameState, apple: (u16, u16), direction: Direction, rng: &mut impl Rng) -> bool {
+    // In tron mode the snake leaves its whole trail behind, and scores for
+    // surviving rather than eating
+    if game.tron {
+        game.score += 1;
+        return false;
+    }
+
+    // Remove oldest segment, unless you ate an apple
+    // Ghost apples only count when entered the way their arrow points
+    let apples = game.apples;
+    let eats = game.head == apple && game.ghost.is_none_or(|d| d == direction);
+    let mut full = false;
+    let grows = if eats && game.teleport {
+        // Each catch in a row is worth more, but the snake doesn't grow
+        game.chase += 1;
+        game.score += game.apple_points(APPLE_POINTS * game.chase);
+        game.apples += 1;
+        game.teleport_apple(rng);
+        game.roll_ghost(rng);
+        false
+    } else if eats {
+        game.score += game.apple_points(APPLE_POINTS);
+        game.apples += 1;
+
+        // New apple position
+        match game.random_apple_cell(rng) {
+            Some(cell) => game.apple = cell,
+            None => full = true,
+        }
+        game.roll_ghost(rng);
+        true
+    } else {
+        game.eat_spare(game.head, rng)
+    };
+
+    // A fixed length snake grows out to it from the start whatever it eats,
+    // then never grows again
+    let grows = match game.fixed_length {
+        Some(length) => game.snake.len() <= length,
+        None => grows,
+    };
+    if !grows {
+        // Remove oldest segment
+        game.snake.pop_front();
+    }
+
+    game.update_streak(game.apples > apples);
+
+    // A few ticks' safety from crashing after eating
+    game.shield = if game.apples > apples {
+        game.shield_ticks
+    } else {
+        game.shield.saturating_sub(1)
+    };
+    game.reach_milestones();
+
+    // Flash the head for a few ticks after eating
+    game.flash = if eats {
+        game.eat_flash
+    } else {
+        game.flash.saturating_sub(1)
+    };
+    full
+}
+
+// Keep the state at the start of a tick to rewind to, as far back as the
+// rewind depth, while there are rewinds left to use
+fn remember(game: &GameState, history: &mut VecDeque<GameState>) {
+    if game.rewinds > 0 {
+        history.push_back(game.clone());
+        if history.len() > game.rewind_depth {
+            history.pop_front();
+        }
+    }
+}
+
+// Go back to the oldest state remembered, using up a rewind, returning whether
+// there was one to go back to
+fn rewind(game: &mut GameState, history: &mut VecDeque<GameState>) -> bool {
+    if game.rewinds == 0 {
+        return false;
+    }
+    let Some(mut past) = history.pop_front() else {
+        return false;
+    };
+    history.clear();
+
+    // The clock, and how the game looks, carry on from now
+    past.rewinds = game.rewinds - 1;
+    past.start = game.start;
+    past.last_input = game.last_input;
+    past.snake_style = game.snake_style;
+    past.apple_style = game.apple_style;
+    past.color = game.color;
+    past.square = game.square;
+    past.colorblind = game.colorblind;
+    past.turns.clear();
+    *game = past;
+    true
+}
+
+// Run the game loop until the game ends
+fn play<I: InputSource, O: Write>(
+    game: &mut GameState,
+    console: &mut Console<I, O>,
+    observer: &mut impl GameObserver,
+    rng: &mut StdRng,
+    history: &mut VecDeque<GameState>,
+) -> GameResult {
+    loop {
+        game.commit_turn();
+        let (head, direction) = (game.head, game.direction);
+
+        if game.quit || console.failed {
+            return GameResult::Quit;
+        }
+
+        if game.time_left() == Some(Duration::ZERO) {
+            return GameResult::TimeUp;
+        }
+
+        // Hold still while asking a question, paused, or when left alone
+        let asking = game.confirm_quit || game.confirm_shrink.is_some();
+        if asking || game.paused.is_some() || idle(game, console) {
+            let delay = game.delay;
+            console.wait(game, delay);
+            continue;
+        }
+        remember(game, history);
+
+        // Running off the edge or into a wall holds the snake still for the
+        // grace period, if there is one, before it dies
+        let next = next_head(head, game.board, direction, game.edges);
+        let walled = next.is_none_or(|(cell, wrapped)| {
+            game.walls.contains(&cell) && !(wrapped && game.wrap_blocked)
+        });
+        if walled && game.shield == 0 && !game.hold_at_wall() {
+            return GameResult::Died(direction);
+        }
+        let Some((new_head, wrapped)) = next.filter(|_| !walled) else {
+            game.shield = game.shield.saturating_sub(1);
+            hold(game, console);
+            continue;
+        };
+        game.grace = None;
+
+        // The head pushes a block along ahead of it, running into one that
+        // won't move stops the snake for this tick, or kills it
+        let stuck = game.blocks.contains(&new_head) && !game.push_block(new_head, direction);
+        if stuck && game.fatal_blocks {
+            return GameResult::Died(direction);
+        }
+
+        // Wrapping into a wall either stops the snake for this tick, or kills it
+        if stuck || wrapped && game.wrap_blocked && game.walls.contains(&new_head) {
+            hold(game, console);
+            continue;
+        }
+
+        // Snake or wall contains new position, collision
+        // Just after eating, the snake passes over snakes and waits at walls
+        let wall = game.walls.contains(&new_head);
+        if game.hits_snake(new_head) || wall || game.on_rival(new_head) {
+            if game.shield == 0 {
+                return GameResult::Died(direction);
+            }
+            if wall {
+                game.shield -= 1;
+                hold(game, console);
+                continue;
+            }
+        }
+        let tail = game.snake[0];
+        let apple = game.apple;
+        let full = advance(game, new_head, direction, rng);
+
+        game.move_rivals(rng);
+        game.update_magnet(rng);
+        game.update_mirror(rng);
+        game.age_apple(apple, rng);
+        if game.shrink_walls(rng) {
+            console.render(game);
+            return GameResult::Died(direction);
+        }
+        game.update_breadcrumbs(apple);
+        game.update_shake();
+        game.update_notice();
+
+        // Leave an afterimage where the tail was, if it's moved on, for half
+        // the tick
+        let moved = (game.snake[0].0, game.snake[0].1) != (tail.0, tail.1);
+        if game.blur && game.color && moved {
+            game.afterimage = Some(tail);
+        }
+
+        // Render
+        console.render(game);
+        observer.tick(game);
+        if game.outlasted_rivals() {
+            return GameResult::Won;
+        }
+
+        // Filling the board wins too, there's nowhere left to grow into
+        if full {
+            return GameResult::Filled;
+        }
+
+        // A campaign stage is cleared once enough apples are eaten
+        if game.apple_goal.is_some_and(|goal| game.apples >= goal) {
+            return GameResult::Cleared;
+        }
+
+        // Fade the afterimage half way through the tick
+        let delay = game.tick_delay();
+        if game.afterimage.is_some() {
+            console.wait(game, delay / 2);
+            game.afterimage = None;
+            console.render(game);
+            console.wait(game, delay.saturating_sub(delay / 2));
+        } else {
+            console.wait(game, delay);
+        }
+    }
+}
+
+// Flash the snake a segment at a time once it's died, if the ripple's turned
+// on, taking no longer than MAX_RIPPLE however long the snake is
+// A key press lights up the rest at once, see `handle_input`
+fn ripple<I: InputSource, O: Write>(game: &mut GameState, console: &mut Console<I, O>) {
+    if game.ripple.is_none() {
+        return;
+    }
+    let len = game.snake.len();
+    let most = MAX_RIPPLE / u32::try_from(len).unwrap_or(u32::MAX);
+    let delay = game.ripple_delay.min(most);
+    for lit in 1..=len {
+        if game.rippled == Some(len) || console.failed {
+            break;
+        }
+        game.rippled = Some(lit);
+        console.render(game);
+        console.wait(game, delay);
+    }
+}
+
+/// Play a game from `game` to the end, reading events from `input` and
+/// drawing frames to `output`, while `observer` watches
+///
+/// Terminal setup, such as raw mode, is left to the caller. Input is polled
+/// on the calling thread while the game waits between ticks, so nothing is
+/// read from `input` once the game ends.
+///
+/// Nothing but `output` is written to, and it's flushed after every frame
+/// and once the game ends, so frames can be piped to a file or captured in
+/// a buffer as well as drawn on a terminal. If writing to it fails, the
+/// game ends there as if it was quit, and nothing more is written.
+pub fn run_game<I, O>(
+    mut game: GameState,
+    input: I,
+    output: O,
+    observer: &mut impl GameObserver,
+) -> (GameResult, GameState)
+where
+    I: InputSource,
+    O: Write,
+{
+    game.start = game.clock.now();
+    game.last_input = game.start;
+    let mut console = Console {
+        input: Some(input),
+        screen: Screen::new(output),
+        throttle: Throttle::default(),
+        failed: false,
+    };
+
+    // Dying with rewinds left goes back a few ticks and carries on
+    let mut rng = StdRng::seed_from_u64(game.seed);
+    let mut history = VecDeque::new();
+    let result = loop {
+        match play(&mut game, &mut console, observer, &mut rng, &mut history) {
+            GameResult::Died(_) if rewind(&mut game, &mut history) => {}
+            result => break result,
+        }
+    };
+
+    if let GameResult::Died(direction) = result {
+        // Render snake about to die
+        game.shaking = 0;
+        let len = game.snake.len();
+        game.snake[len - 1].2 = SegmentType::from_next(game.snake[len - 1].3, direction);
+        ripple(&mut game, &mut console);
+    }
+
+    // Nothing else is drawn once the game's over, so the last frame stays
+    game.shaking = 0;
+    if !matches!(result, GameResult::Quit) {
+        console.render(&game);
+    }
+    if !console.failed {
+        let output = &mut console.screen;
+        let ended = match result {
+            GameResult::Died(_) => game_over(output, &game),
+            GameResult::TimeUp => time_up(output, &game),
+            GameResult::Won => you_win(output, &game),
+            GameResult::Filled => board_filled(output, &game),
+            GameResult::Cleared => cleared(output, &game),
+            GameResult::Quit => quit(output, &game),
+        };
+        // The game's over either way, whether or not that could be said
+        ended.ok();
+    }
+    observer.finish(&game, result);
+
+    (result, game)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::GameStateBuilder;
+    use crate::clock::{Clock, ManualClock};
+    use crate::game::{Edge, Edges};
+    use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+    use std::io;
+    use std::time::Instant;
+
+    // What render_all draws for the snake, the rows and columns given
+    fn drawn(game: &GameState, columns: std::ops::Range<u16>, rows: u16) -> Vec<String> {
+        (0..rows)
+            .map(|y| {
+                columns
+                    .clone()
+                    .map(|x| {
+                        game.snake
+                            .iter()
+                            .find(|s| (s.0, s.1) == (x, y))
+                            .map_or(' ', |s| s.2.display(game.snake_style))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn s_shaped_snake_joins_up_at_every_turn() {
+        let mut game = GameState::new();
+        game.snake = (0..7)
+            .map(|x| Segment(x, 0, SegmentType::EastWest, Direction::East))
+            .collect();
+        game.head = (6, 0);
+        game.apple = (9, 9);
+
+        // Down, back, down and off again
+        let turns = [
+            Direction::East,
+            Direction::South,
+            Direction::West,
+            Direction::West,
+            Direction::South,
+            Direction::East,
+            Direction::East,
+        ];
+        for direction in turns {
+            let (x, y) = game.head;
+            let new_head = match direction {
+                Direction::North => (x, y - 1),
+                Direction::South => (x, y + 1),
+                Direction::East => (x + 1, y),
+                Direction::West => (x - 1, y),
+            };
+            advance(&mut game, new_head, direction, &mut thread_rng());
+        }
+
+        let joints: Vec<_> = game.snake.iter().map(|s| ((s.0, s.1), s.2)).collect();
+        assert_eq!(
+            joints,
+            [
+                // The tail is straight, it no longer joins the cell it left
+                ((7, 0), SegmentType::NorthSouth),
+                ((7, 1), SegmentType::NorthWest),
+                ((6, 1), SegmentType::EastWest),
+                ((5, 1), SegmentType::SouthEast),
+                ((5, 2), SegmentType::NorthEast),
+                ((6, 2), SegmentType::EastWest),
+                ((7, 2), SegmentType::EastWest),
+            ]
+        );
+        assert_eq!(drawn(&game, 4..8, 3), ["   │", " ╭─╯", " ╰──"]);
+    }
+
+    #[test]
+    fn next_head_says_when_it_wraps() {
+        let board = (10, 8);
+        let wrap = Edges::all(Edge::Wrap);
+        // Moving inside the board doesn't wrap
+        assert_eq!(
+            next_head((3, 3), board, Direction::East, wrap),
+            Some(((4, 3), false))
+        );
+        // Crossing an edge does, so a wall on the far side can block the snake
+        assert_eq!(
+            next_head((9, 3), board, Direction::East, wrap),
+            Some(((0, 3), true))
+        );
+        assert_eq!(
+            next_head((3, 0), board, Direction::North, wrap),
+            Some(((3, 7), true))
+        );
+        // Or comes back in on the row asked for
+        let edges = Edges {
+            east: Edge::WrapTo(5),
+            ..wrap
+        };
+        assert_eq!(
+            next_head((9, 3), board, Direction::East, edges),
+            Some(((0, 5), true))
+        );
+        // Without wrapping there's nowhere to go
+        assert_eq!(
+            next_head((0, 3), board, Direction::West, Edges::all(Edge::Wall)),
+            None
+        );
+    }
+
+    #[test]
+    fn ghost_apples_are_only_eaten_going_their_way() {
+        let mut game = GameState::new();
+        game.snake = (0..3)
+            .map(|x| Segment(x, 0, SegmentType::EastWest, Direction::East))
+            .collect();
+        game.head = (2, 0);
+        game.apple = (3, 0);
+        game.ghost = Some(Direction::South);
+
+        // Going east passes straight through it
+        advance(&mut game, (3, 0), Direction::East, &mut thread_rng());
+        assert_eq!((game.snake.len(), game.apples), (3, 0));
+        assert_eq!(game.apple, (3, 0));
+
+        // Going south into it eats it
+        game.apple = (4, 1);
+        advance(&mut game, (4, 0), Direction::East, &mut thread_rng());
+        advance(&mut game, (4, 1), Direction::South, &mut thread_rng());
+        assert_eq!((game.snake.len(), game.apples), (4, 1));
+    }
+
+    #[test]
+    fn fixed_length_snake_grows_out_then_stops() {
+        let mut game = GameStateBuilder::new()
+            .seed(1)
+            .board(20, 5)
+            .time_attack(Some(4))
+            .build()
+            .unwrap();
+        game.apple = (6, 0);
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut lengths = Vec::new();
+        for x in 2..7 {
+            advance(&mut game, (x, 0), Direction::East, &mut rng);
+            lengths.push(game.snake.len());
+        }
+        // Eating the apple at (6, 0) still scores, without growing
+        assert_eq!(lengths, [3, 4, 4, 4, 4]);
+        assert_eq!(game.apples, 1);
+    }
+
+    #[test]
+    fn shield_lasts_a_few_ticks_after_eating() {
+        let mut game = GameStateBuilder::new()
+            .seed(1)
+            .board(20, 5)
+            .shield(2)
+            .build()
+            .unwrap();
+        game.snake = (0..3)
+            .map(|x| Segment(x, 0, SegmentType::EastWest, Direction::East))
+            .collect();
+        game.head = (2, 0);
+        game.apple = (3, 0);
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut shields = Vec::new();
+        for x in 3..7 {
+            game.apple = if x == 3 { (3, 0) } else { (19, 4) };
+            advance(&mut game, (x, 0), Direction::East, &mut rng);
+            shields.push(game.shield);
+        }
+        assert_eq!(shields, [2, 1, 0, 0]);
+    }
+
+    // Events sent at set times after the game starts, by a clock that only
+    // moves when the game waits
+    pub(crate) struct Script {
+        clock: Arc<ManualClock>,
+        start: Instant,
+        events: VecDeque<(Duration, Event)>,
+    }
+
+    impl Script {
+        pub(crate) fn new(clock: &Arc<ManualClock>, events: Vec<(Duration, Event)>) -> Self {
+            Script {
+                clock: Arc::clone(clock),
+                start: clock.now(),
+                events: events.into(),
+            }
+        }
+    }
+
+    impl InputSource for Script {
+        fn poll(&mut self, timeout: Duration) -> io::Result<Option<Event>> {
+            let now = self.clock.now();
+            match self.events.front() {
+                Some(&(at, _)) if self.start + at <= now + timeout => {
+                    self.clock
+                        .advance((self.start + at).saturating_duration_since(now));
+                    Ok(self.events.pop_front().map(|(_, event)| event))
+                }
+                _ => Ok(None),
+            }
+        }
+    }
+
+    fn key(c: char) -> Event {
+        Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE))
+    }
+
+    // Left alone for a second, then a key pressed after ten
+    fn attract(c: char) -> (GameResult, GameState) {
+        let clock = Arc::new(ManualClock::new());
+        let game = GameStateBuilder::new()
+            .seed(1)
+            .board(30, 10)
+            .apple(20, 5)
+            .idle_pause(Some(Duration::from_secs(1)))
+            .attract(true)
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+        let input = Script::new(&clock, vec![(Duration::from_secs(10), key(c))]);
+        run_game(game, input, io::sink(), &mut ())
+    }
+
+    #[test]
+    fn left_alone_the_autopilot_takes_over() {
+        let (result, game) = attract('x');
+        assert!(matches!(result, GameResult::Quit));
+        assert!(game.autopilot);
+        assert!(game.apples > 0);
+        // Taken over by a key, for a new game
+        assert!(game.attracting);
+    }
+
+    #[test]
+    fn q_ends_a_demo_for_good() {
+        let (result, game) = attract('q');
+        assert!(matches!(result, GameResult::Quit));
+        assert!(!game.attracting);
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    // A straight snake along `cells`, tail first, heading `direction`
+    fn lay(game: &mut GameState, cells: &[(u16, u16)], direction: Direction) {
+        let segment = SegmentType::from_dir(direction);
+        game.snake = cells
+            .iter()
+            .map(|&(x, y)| Segment(x, y, segment, direction))
+            .collect();
+        game.head = *cells.last().unwrap();
+        game.direction = direction;
+    }
+
+    // Play a game from `builder` with `setup` done to it first, against a
+    // script of events
+    fn play_out(
+        builder: GameStateBuilder,
+        setup: impl FnOnce(&mut GameState),
+        events: Vec<(Duration, Event)>,
+        observer: &mut impl GameObserver,
+    ) -> (GameResult, GameState) {
+        let clock = Arc::new(ManualClock::new());
+        let mut game = builder.clock(clock.clone()).build().unwrap();
+        setup(&mut game);
+        run_game(game, Script::new(&clock, events), io::sink(), observer)
+    }
+
+    // Where the head is after each tick
+    #[derive(Default)]
+    struct Heads(Vec<(u16, u16)>);
+
+    impl GameObserver for Heads {
+        fn tick(&mut self, game: &GameState) {
+            self.0.push(game.head);
+        }
+    }
+
+    // The first three ticks, with keys pressed at times into the game, the
+    // ticks coming every 250ms
+    fn first_ticks(keys: &[(u64, char)]) -> Vec<(u16, u16)> {
+        let mut events: Vec<_> = keys.iter().map(|&(at, c)| (ms(at), key(c))).collect();
+        events.push((ms(600), key('q')));
+        let builder = GameStateBuilder::new().seed(1).apple(5, 5);
+        let mut heads = Heads::default();
+        play_out(builder, |_| {}, events, &mut heads);
+        heads.0
+    }
+
+    #[test]
+    fn turns_apply_from_the_next_tick() {
+        assert_eq!(first_ticks(&[(240, 'j')]), [(2, 0), (2, 1), (2, 2)]);
+        assert_eq!(first_ticks(&[(260, 'j')]), [(2, 0), (3, 0), (3, 1)]);
+    }
+
+    #[test]
+    fn turns_in_one_tick_apply_a_tick_each() {
+        assert_eq!(
+            first_ticks(&[(100, 'j'), (110, 'h')]),
+            [(2, 0), (2, 1), (1, 1)]
+        );
+    }
+
+    // Reaching the east wall on the first tick, with three ticks' grace
+    fn at_wall(events: Vec<(Duration, Event)>) -> (GameResult, GameState, Heads) {
+        let builder = GameStateBuilder::new().seed(1).apple(5, 5).wall_grace(3);
+        let setup = |game: &mut GameState| lay(game, &[(7, 0), (8, 0)], Direction::East);
+        let mut heads = Heads::default();
+        let (result, game) = play_out(builder, setup, events, &mut heads);
+        (result, game, heads)
+    }
+
+    #[test]
+    fn turning_in_the_grace_period_saves_the_snake() {
+        let (result, game, heads) = at_wall(vec![(ms(600), key('j')), (ms(900), key('q'))]);
+        assert!(matches!(result, GameResult::Quit));
+        assert_eq!(game.head, (9, 1));
+        assert_eq!(heads.0, [(9, 0), (9, 1)]);
+    }
+
+    #[test]
+    fn snake_dies_once_the_grace_period_is_up() {
+        let (result, game, heads) = at_wall(vec![(ms(2000), key('j'))]);
+        assert!(matches!(result, GameResult::Died(Direction::East)));
+        assert_eq!(game.head, (9, 0));
+        assert_eq!(heads.0, [(9, 0)]);
+    }
+
+    // Eating an apple two cells from the east wall, with three ticks' shield
+    fn shielded(events: Vec<(Duration, Event)>) -> (GameResult, GameState) {
+        let builder = GameStateBuilder::new().seed(1).apple(8, 0).shield(3);
+        let setup = |game: &mut GameState| lay(game, &[(6, 0), (7, 0)], Direction::East);
+        play_out(builder, setup, events, &mut ())
+    }
+
+    #[test]
+    fn shield_holds_the_snake_at_a_wall() {
+        let (result, game) = shielded(vec![(ms(850), key('j')), (ms(1100), key('q'))]);
+        assert!(matches!(result, GameResult::Quit));
+        assert_eq!(game.apples, 1);
+        assert_eq!(game.head, (9, 1));
+    }
+
+    #[test]
+    fn wall_is_fatal_once_the_shield_wears_off() {
+        let (result, game) = shielded(vec![(ms(2000), key('j'))]);
+        assert!(matches!(result, GameResult::Died(Direction::East)));
+        assert_eq!(game.head, (9, 0));
+        assert_eq!(game.shield, 0);
+    }
+
+    // The snake's cells after twenty seconds on autopilot
+    fn autopiloted(builder: GameStateBuilder) -> (u64, Vec<(u16, u16)>) {
+        let builder = builder
+            .autopilot(true)
+            .time_limit(Some(Duration::from_secs(20)));
+        let (_, game) = play_out(builder, |_| {}, vec![], &mut ());
+        (game.seed, game.snake.iter().map(|s| (s.0, s.1)).collect())
+    }
+
+    #[test]
+    fn stored_seed_plays_the_same_game_again() {
+        let (seed, snake) = autopiloted(GameStateBuilder::new());
+        assert!(snake.len() > 2);
+        assert_eq!(
+            autopiloted(GameStateBuilder::new().seed(seed)),
+            (seed, snake)
+        );
+    }
+
+    // Heading east along the top row towards a wall just past the east edge
+    fn wrap_into_wall(blocked: bool) -> (GameResult, GameState) {
+        let builder = GameStateBuilder::new()
+            .seed(1)
+            .wrap(true)
+            .wrap_blocked(blocked)
+            .apple(5, 5);
+        let setup = |game: &mut GameState| {
+            lay(game, &[(6, 0), (7, 0)], Direction::East);
+            game.walls.insert((0, 0));
+        };
+        play_out(builder, setup, vec![(ms(1100), key('q'))], &mut ())
+    }
+
+    #[test]
+    fn wrapping_into_a_wall_crashes() {
+        let (result, game) = wrap_into_wall(false);
+        assert!(matches!(result, GameResult::Died(Direction::East)));
+        assert_eq!(game.head, (9, 0));
+    }
+
+    #[test]
+    fn wrapping_into_a_wall_can_stop_the_snake_instead() {
+        let (result, game) = wrap_into_wall(true);
+        assert!(matches!(result, GameResult::Quit));
+        assert_eq!(game.head, (9, 0));
+        assert_eq!(game.snake.len(), 2);
+    }
+
+    // Output that can't be written to, like a terminal that's gone away
+    struct Broken;
+
+    impl Write for Broken {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failing_output_ends_the_game() {
+        let clock = Arc::new(ManualClock::new());
+        let game = GameStateBuilder::new()
+            .seed(1)
+            .apple(5, 5)
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+        let (result, game) = run_game(game, Script::new(&clock, vec![]), Broken, &mut ());
+        assert!(matches!(result, GameResult::Quit));
+        // Drawing the first tick fails, so the snake gets no further
+        assert_eq!(game.head, (2, 0));
+    }
+
+    // Where the afterimage and the tail are after each tick
+    #[derive(Default)]
+    struct Afterimages(Vec<[(u16, u16); 2]>);
+
+    impl GameObserver for Afterimages {
+        fn tick(&mut self, game: &GameState) {
+            let afterimage = game.afterimage.expect("an afterimage every tick");
+            let tail = game.snake[0];
+            self.0
+                .push([(afterimage.0, afterimage.1), (tail.0, tail.1)]);
+        }
+    }
+
+    #[test]
+    fn afterimage_trails_the_tail() {
+        let clock = Arc::new(ManualClock::new());
+        let game = GameStateBuilder::new()
+            .seed(1)
+            .apple(5, 5)
+            .blur(true)
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+        let mut seen = Afterimages::default();
+        run_game(game, Script::new(&clock, vec![]), io::sink(), &mut seen);
+        assert!(seen.0.len() > 2);
+        for [afterimage, (x, y)] in seen.0 {
+            assert_eq!(afterimage, (x - 1, y));
+        }
+    }
+}
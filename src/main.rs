@@ -2,474 +2,1080 @@
 
 // TODO: just-direction segments
 
-use std::collections::VecDeque;
-use std::io::{stdout, Write};
-use std::sync::{Arc, Mutex};
+use std::fs::File;
+use std::io::{self, sink, stdout, BufWriter, IsTerminal, Write};
+use std::num::ParseIntError;
+use std::panic;
+use std::path::{Path, PathBuf};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
 
 use crossterm::cursor::{Hide, MoveTo, Show};
-use crossterm::event::{read, Event, KeyCode};
+use crossterm::event::{
+    self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEvent, KeyModifiers,
+};
 use crossterm::execute;
-use crossterm::style::{style, Color, Stylize};
-use crossterm::terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType};
+use crossterm::terminal::{self, disable_raw_mode, enable_raw_mode, Clear, ClearType};
+
+use snake_crossterm::builder::GameStateBuilder;
+use snake_crossterm::campaign;
+use snake_crossterm::config::{self, Config};
+use snake_crossterm::daily;
+use snake_crossterm::game::{
+    AppleStyle, Borders, Edge, Edges, GameResult, GameState, Keys, QueueOverflow, Region, Ripple,
+    RivalSkill, ShrinkPolicy, SnakeStyle, MAX_BOARD,
+};
+use snake_crossterm::input::{CrosstermInput, InputSource};
+use snake_crossterm::level::Level;
+use snake_crossterm::observer::Announcer;
+use snake_crossterm::position::Position;
+use snake_crossterm::replay::{self, Frame, Recorder};
+use snake_crossterm::run_game;
+use snake_crossterm::stats::{self, GameRecord, Mode, Session};
+
+// Frames are written through a buffer this big and flushed once each, so
+// even a big board goes out in one write instead of a write per kilobyte
+const FRAME_BUFFER: usize = 64 * 1024;
+
+// How long the screen between campaign levels waits for a key
+const STAGE_PAUSE: Duration = Duration::from_secs(3);
+
+// Seconds a kiosk game is left alone before the autopilot takes over
+const ATTRACT_AFTER: u64 = 15;
+
+/// Command line snake game
+#[derive(Debug, Parser)]
+#[command(version, about, disable_version_flag = true)]
+#[allow(clippy::struct_excessive_bools)]
+struct Cli {
+    /// Print the version and the config file in use, and exit
+    #[arg(short = 'V', long)]
+    version: bool,
+
+    /// Stop the snake instead of killing it when it wraps into a wall
+    #[arg(long)]
+    wrap_blocked: bool,
+
+    /// Use colors and an apple glyph that don't rely on telling red from green
+    #[arg(long)]
+    colorblind: bool,
+
+    /// Describe the game in lines of text for screen readers, instead of drawing the board
+    #[arg(long)]
+    announce: bool,
+
+    /// Read default settings from this TOML file [default: config.toml in the config directory]
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Append finished games to this CSV file [default: stats.csv in the data directory]
+    #[arg(long, value_name = "PATH")]
+    stats: Option<PathBuf>,
+
+    /// Chance each tick of an apple magnet power-up appearing, from 0 to 1
+    #[arg(long, value_name = "CHANCE", default_value_t = 0.0)]
+    magnet_rate: f64,
+
+    /// Draw each cell two columns wide so the board looks square
+    #[arg(long)]
+    square: bool,
+
+    /// Score as much as possible before this many seconds run out
+    #[arg(long, value_name = "SECONDS")]
+    time_limit: Option<u64>,
+
+    /// Show an arrow pointing to the apple, and its distance, below the board
+    #[arg(long)]
+    compass: bool,
+
+    /// Seed for the random number generator, the same seed gives the same apples
+    #[arg(long, conflicts_with = "daily")]
+    seed: Option<u64>,
+
+    /// Play today's daily challenge, the same for everyone on the same (UTC) day
+    #[arg(long)]
+    daily: bool,
+
+    /// Move the apple somewhere else if it isn't eaten within this many ticks
+    #[arg(long, value_name = "TICKS")]
+    apple_timeout: Option<u32>,
+
+    /// Scroll the board with the snake when it doesn't fit in the terminal
+    #[arg(long)]
+    scroll: bool,
+
+    /// Close the walls in by a ring every this many seconds
+    #[arg(long, value_name = "SECONDS")]
+    shrink_every: Option<u64>,
+
+    /// Scatter this many random walls, never cutting the board in two
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    obstacles: usize,
+
+    /// Cells to keep clear between random walls, and around the snake and apple
+    #[arg(long, value_name = "CELLS", default_value_t = 1)]
+    obstacle_gap: u16,
+
+    /// How steeply apple points rise as the game speeds up, 0 turns it off
+    #[arg(long, value_name = "SLOPE", default_value_t = 0.5)]
+    speed_bonus: f64,
+
+    /// Leave a dim afterimage where the tail has just been as the snake moves (needs color)
+    #[arg(long)]
+    blur: bool,
+
+    /// Pause when no key has been pressed for this many seconds (not in timed games)
+    #[arg(long, value_name = "SECONDS")]
+    idle_pause: Option<u64>,
+
+    /// Start from a position shared with `x`
+    #[arg(long, value_name = "CODE", conflicts_with = "level")]
+    position: Option<String>,
+
+    /// Hardcore mode: no wrapping, a fixed speed, and its own leaderboard
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "autopilot",
+            "reversed",
+            "swarm",
+            "magnet_rate",
+            "time_attack",
+            "rewinds",
+            "wall_grace",
+            "shield",
+            "wrap_blocked",
+            "edges",
+            "wall_wrap",
+            "accept_paste",
+        ]
+    )]
+    hardcore: bool,
+
+    /// Show the best games played in a mode (casual, hardcore, tron or daily) and exit
+    #[arg(long, value_name = "MODE")]
+    leaderboard: Option<Mode>,
+
+    /// Ticks to flash the head for after eating an apple, 0 turns it off
+    #[arg(long, value_name = "TICKS", default_value_t = 1)]
+    eat_flash: u32,
+
+    /// Put the first apple here instead of on a random free cell
+    #[arg(long, value_name = "X,Y", value_parser = parse_cell)]
+    apple: Option<(u16, u16)>,
+
+    /// Record the game tick by tick to this file
+    #[arg(long, value_name = "PATH")]
+    record: Option<PathBuf>,
+
+    /// Play back a recording instead of playing
+    #[arg(long, value_name = "PATH", conflicts_with = "record")]
+    replay: Option<PathBuf>,
+
+    /// What the top, bottom, right and left edges do, each wall, wrap, or the
+    /// row or column to come back in at on the opposite edge
+    #[arg(long, value_name = "N,S,E,W", value_parser = parse_edges)]
+    edges: Option<Edges>,
+
+    /// Chance (0 to 1) of each new apple being a ghost, only eaten moving the way its arrow points
+    #[arg(long, value_name = "CHANCE", default_value_t = 0.0)]
+    ghost_rate: f64,
+
+    /// Share the board with this many computer controlled snakes, outlast them all to win
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    swarm: usize,
+
+    /// Turn as soon as a key is pressed, the last turn in a tick winning,
+    /// instead of one queued turn per tick
+    #[arg(long)]
+    immediate_turns: bool,
+
+    /// Show the snake's length next to its head
+    #[arg(long)]
+    show_length: bool,
+
+    /// Times dying rewinds the game a few ticks instead of ending it
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    rewinds: u32,
+
+    /// Ticks to go back on each rewind
+    #[arg(long, value_name = "TICKS", default_value_t = 5)]
+    rewind_depth: usize,
+
+    /// Time attack: the snake stays this long, eat as many apples as you can
+    /// before the time limit (a minute unless given) runs out
+    #[arg(long, value_name = "LENGTH")]
+    time_attack: Option<usize>,
+
+    /// Speed up by this fraction of the delay for each apple eaten in a row,
+    /// 0 turns streaks off
+    #[arg(long, value_name = "FRACTION", default_value_t = 0.0)]
+    streak_boost: f64,
+
+    /// How much of the streak speed-up wears off each tick
+    #[arg(long, value_name = "FRACTION", default_value_t = 0.02)]
+    streak_decay: f64,
+
+    /// What shrinking the board into the snake does: refuse, clamp (cut off
+    /// whatever's past the new edges) or confirm (ask before clamping)
+    #[arg(long, value_name = "POLICY", default_value = "refuse")]
+    shrink: ShrinkPolicy,
+
+    /// Running into a block that can't be pushed kills the snake, instead of
+    /// stopping it
+    #[arg(long)]
+    fatal_blocks: bool,
+
+    /// Ticks the snake waits at a wall before dying, turning away in time
+    /// saves it
+    #[arg(long, value_name = "TICKS", default_value_t = 0)]
+    wall_grace: u32,
+
+    /// Leave a fading trail where the apple has been, lasting this many ticks
+    #[arg(long, value_name = "TICKS", default_value_t = 0)]
+    breadcrumbs: u32,
+
+    /// Arrow keys (and hjkl) do the opposite of what they say
+    #[arg(long)]
+    reversed: bool,
+
+    /// Chance each tick of a mirror power-up appearing, from 0 to 1, which
+    /// reverses the controls for a while once collected
+    #[arg(long, value_name = "CHANCE", default_value_t = 0.0)]
+    mirror_rate: f64,
+
+    /// Bonus points for first growing to a length, can be given more than once
+    #[arg(long, value_name = "LENGTH:BONUS", value_parser = parse_milestone)]
+    milestone: Vec<(usize, u32)>,
+
+    /// Only put apples in this rectangle, its top left cell then its size
+    #[arg(long, value_name = "X,Y,W,H", value_parser = parse_region)]
+    spawn_region: Option<Region>,
+
+    /// Shake the screen for a moment when the head comes alongside the
+    /// snake's body or a wall
+    #[arg(long)]
+    shake: bool,
+
+    /// Start with the rulers showing
+    #[arg(long)]
+    rulers: bool,
+
+    /// When the snake dies, flash it red a segment at a time starting from
+    /// its head or its tail
+    #[arg(long, value_name = "END")]
+    ripple: Option<Ripple>,
+
+    /// Milliseconds between segments of the death ripple, which is cut short
+    /// to two seconds in all
+    #[arg(long, value_name = "MS", default_value_t = 40)]
+    ripple_delay: u64,
+
+    /// Load a position code pasted into the game, in place of the board
+    #[arg(long, conflicts_with = "hardcore")]
+    accept_paste: bool,
+    /// Let the snake steer itself towards the apples, games it plays aren't
+    /// logged to the stats file
+    #[arg(long)]
+    autopilot: bool,
+
+    /// Unattended mode: start a new game this many seconds (default 5) after
+    /// each one ends, until q is pressed. A game left alone for the idle
+    /// pause (default 15 seconds) plays itself until a key is pressed
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        num_args = 0..=1,
+        default_missing_value = "5",
+        conflicts_with = "record"
+    )]
+    kiosk: Option<u64>,
+
+    /// Most turns that can be queued up ahead of the snake
+    #[arg(long, value_name = "N", default_value_t = 2)]
+    turn_queue: usize,
+
+    /// Which turn gives way when the turn queue is full: drop-oldest or
+    /// drop-newest
+    #[arg(long, value_name = "POLICY", default_value = "drop-newest")]
+    queue_overflow: QueueOverflow,
+
+    /// Practice aid: show where the snake's heading over this many ticks,
+    /// and mark what it would crash into
+    #[arg(long, value_name = "TICKS", default_value_t = 0)]
+    lookahead: u32,
 
-use rand::prelude::*;
+    /// Keep apples at least this many cells in from walls and edges that
+    /// don't wrap, closer only when there's no room
+    #[arg(long, value_name = "CELLS", default_value_t = 0)]
+    apple_inset: u16,
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum Direction {
-    North,
-    South,
-    East,
-    West,
+    /// A line of text above the game, `{seed}` and `{difficulty}` are filled
+    /// in
+    #[arg(long, value_name = "TEXT", default_value = "")]
+    title: String,
+
+    /// A line of text under the game, filled in like the title
+    #[arg(long, value_name = "TEXT", default_value = "")]
+    footer: String,
+
+    /// Draw the snake's straight segments either side of a wrapping edge as
+    /// arrows, so it doesn't look cut in two
+    #[arg(long)]
+    wrap_arrows: bool,
+
+    /// For this many ticks after eating, the snake passes over itself and
+    /// waits at walls instead of crashing
+    #[arg(long, value_name = "TICKS", default_value_t = 0)]
+    shield: u32,
+
+    /// Which sides of the board to draw a border on: none, or any of top,
+    /// bottom, left and right separated by commas
+    #[arg(long, value_name = "SIDES", default_value = "bottom,right")]
+    border: Borders,
+
+    /// Width of the board in cells
+    #[arg(long, value_name = "CELLS", default_value_t = 10, conflicts_with_all = ["level", "position"])]
+    width: u16,
+
+    /// Height of the board in cells
+    #[arg(long, value_name = "CELLS", default_value_t = 10, conflicts_with_all = ["level", "position"])]
+    height: u16,
+
+    /// Milliseconds between ticks, lower is faster
+    #[arg(long, value_name = "MS", default_value_t = 250)]
+    speed: u64,
+
+    /// Start with wall wrapping on, every edge leading round to the opposite one
+    #[arg(long, conflicts_with = "edges")]
+    wall_wrap: bool,
+
+    /// How the snake is drawn: curved, sharp, block or ascii
+    #[arg(long, value_name = "STYLE", default_value = "curved")]
+    snake_style: SnakeStyle,
+
+    /// How the apple is drawn: filled, outline, block or ascii
+    #[arg(long, value_name = "STYLE", default_value = "filled")]
+    apple_style: AppleStyle,
+
+    /// Start with color off
+    #[arg(long)]
+    no_color: bool,
+
+    /// Size the board to fill the terminal, and follow it as the terminal is
+    /// resized
+    #[arg(long, conflicts_with_all = ["width", "height", "level", "position", "scroll"])]
+    fit_terminal: bool,
+
+    /// Take this many milliseconds off the delay every few apples, 0 turns
+    /// speeding up off
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    speed_up: u64,
+
+    /// Apples between each speed-up
+    #[arg(long, value_name = "APPLES", default_value_t = 5)]
+    speed_up_every: u32,
+
+    /// Fewest milliseconds between ticks speeding up can reach
+    #[arg(long, value_name = "MS", default_value_t = 60)]
+    speed_floor: u64,
+
+    /// Play the bundled levels in turn, each with more walls and a faster
+    /// snake, moving on after eating enough apples
+    #[arg(long, conflicts_with_all = ["level", "position", "width", "height", "fit_terminal", "speed_up", "replay"])]
+    campaign: bool,
+
+    /// Apples to eat to clear each campaign level
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    campaign_apples: u32,
+
+    /// How well the swarm's snakes play: easy (they wander now and then),
+    /// normal or hard (they keep out of spaces too small for them)
+    #[arg(long, value_name = "SKILL", default_value = "normal")]
+    rival_skill: RivalSkill,
+
+    /// Tron mode: the snake never shrinks and there are no apples, score a
+    /// point for every tick survived
+    #[arg(long, conflicts_with_all = ["campaign", "time_attack"])]
+    tron: bool,
+
+    /// Load the board from a level file
+    #[arg(long, value_name = "PATH")]
+    level: Option<PathBuf>,
+
+    // Only ever set from the config file
+    #[arg(skip)]
+    keys: Keys,
 }
 
-// A type of segment in the snake, for printing
-#[derive(Debug, Clone, Copy)]
-enum SegmentType {
-    NorthSouth,
-    NorthEast,
-    NorthWest,
-    SouthEast,
-    SouthWest,
-    EastWest,
+// A board cell written as X,Y
+fn parse_cell(s: &str) -> Result<(u16, u16), String> {
+    let (x, y) = s.split_once(',').ok_or("expected X,Y")?;
+    let parse = |n: &str| n.trim().parse::<u16>().map_err(|e| e.to_string());
+    Ok((parse(x)?, parse(y)?))
 }
 
-impl SegmentType {
-    fn from_next(a: Direction, b: Direction) -> SegmentType {
-        match a {
-            Direction::North => match b {
-                Direction::North => SegmentType::NorthSouth,
-                Direction::South => panic!(),
-                Direction::East => SegmentType::SouthEast,
-                Direction::West => SegmentType::SouthWest,
-            },
-            Direction::South => match b {
-                Direction::North => panic!(),
-                Direction::South => SegmentType::NorthSouth,
-                Direction::East => SegmentType::NorthEast,
-                Direction::West => SegmentType::NorthWest,
-            },
-            Direction::East => match b {
-                Direction::North => SegmentType::NorthWest,
-                Direction::South => SegmentType::SouthWest,
-                Direction::East => SegmentType::EastWest,
-                Direction::West => panic!(),
-            },
-            Direction::West => match b {
-                Direction::North => SegmentType::NorthEast,
-                Direction::South => SegmentType::SouthEast,
-                Direction::East => panic!(),
-                Direction::West => SegmentType::EastWest,
-            },
-        }
-    }
+// A rectangle of the board written as X,Y,W,H
+fn parse_region(s: &str) -> Result<Region, String> {
+    let numbers = s
+        .split(',')
+        .map(|n| n.trim().parse::<u16>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<u16>, _>>()?;
+    let [x, y, width, height] = numbers[..] else {
+        return Err("expected X,Y,W,H".to_string());
+    };
+    Ok(Region {
+        origin: (x, y),
+        size: (width, height),
+    })
+}
+
+// A length milestone written as LENGTH:BONUS
+fn parse_milestone(s: &str) -> Result<(usize, u32), String> {
+    let (length, bonus) = s.split_once(':').ok_or("expected LENGTH:BONUS")?;
+    Ok((
+        length
+            .trim()
+            .parse()
+            .map_err(|e: ParseIntError| e.to_string())?,
+        bonus
+            .trim()
+            .parse()
+            .map_err(|e: ParseIntError| e.to_string())?,
+    ))
+}
+
+// Four edge policies, top, bottom, right then left
+fn parse_edges(s: &str) -> Result<Edges, String> {
+    let edges = s
+        .split(',')
+        .map(|edge| edge.trim().parse())
+        .collect::<Result<Vec<Edge>, _>>()?;
+    let [north, south, east, west] = edges[..] else {
+        return Err("expected four edges".to_string());
+    };
+    Ok(Edges {
+        north,
+        south,
+        east,
+        west,
+    })
+}
 
-    fn from_dir(a: Direction) -> SegmentType {
-        match a {
-            Direction::North | Direction::South => SegmentType::NorthSouth,
-            Direction::East | Direction::West => SegmentType::EastWest,
+// Print the best games in a mode from the stats log
+fn show_leaderboard(path: &Path, mode: Mode) {
+    let records = match stats::load(path) {
+        Ok(records) => records,
+        Err(e) => {
+            eprintln!("Couldn't read stats from {}: {e}", path.display());
+            std::process::exit(1);
         }
+    };
+    let best = stats::leaderboard(&records, mode, stats::HIGH_SCORES);
+    if best.is_empty() {
+        println!("No {mode} games yet");
+    }
+    for (rank, record) in (1..).zip(best) {
+        println!("{rank:>2}. {record}");
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum SnakeStyle {
-    CurvedLine,
-    SharpLine,
-    Block,
-    Ascii,
+// Throw away keys pressed before a prompt was up, which were meant for the
+// game that's just ended
+fn discard_pending() {
+    while let Ok(Some(_)) = CrosstermInput.poll(Duration::ZERO) {}
 }
 
-impl SnakeStyle {
-    fn next(self) -> SnakeStyle {
-        match self {
-            SnakeStyle::CurvedLine => SnakeStyle::SharpLine,
-            SnakeStyle::SharpLine => SnakeStyle::Block,
-            SnakeStyle::Block => SnakeStyle::Ascii,
-            SnakeStyle::Ascii => SnakeStyle::CurvedLine,
+// Ask for a name to go with a high score, typed under the game over text
+// Enter keeps it, Esc or Ctrl-C leaves the score without one
+fn ask_name() -> String {
+    discard_pending();
+    let mut name = String::new();
+    print!("\r\n\r\nNew high score! Name: ");
+    execute!(stdout(), Show).unwrap();
+    while let Ok(event) = event::read() {
+        let Event::Key(k) = event else { continue };
+        match k.code {
+            KeyCode::Enter => break,
+            KeyCode::Char('c') if k.modifiers.contains(KeyModifiers::CONTROL) => {
+                name.clear();
+                break;
+            }
+            KeyCode::Esc => {
+                name.clear();
+                break;
+            }
+            KeyCode::Backspace if name.pop().is_some() => print!("\u{8} \u{8}"),
+            KeyCode::Char(c) if stats::name_char(c) && name.chars().count() < stats::NAME_LEN => {
+                name.push(c);
+                print!("{c}");
+            }
+            _ => {}
         }
+        stdout().flush().unwrap();
     }
+    execute!(stdout(), Hide).unwrap();
+    name.trim().to_string()
 }
 
-#[derive(Debug, Clone, Copy)]
-enum AppleStyle {
-    Filled,
-    Outline,
-    Block,
-    Ascii,
+// The leaderboard for the mode of the game just finished, the last record,
+// marking it if it made it
+fn high_scores(records: &[GameRecord]) {
+    let Some(latest) = records.last() else {
+        return;
+    };
+    print!("\r\n\r\nHigh scores ({})", latest.mode);
+    let best = stats::leaderboard(records, latest.mode, stats::HIGH_SCORES);
+    for (rank, record) in (1..).zip(best) {
+        let mark = if std::ptr::eq(record, latest) {
+            '>'
+        } else {
+            ' '
+        };
+        print!("\r\n{mark}{rank:>2}. {record}");
+    }
+    stdout().flush().unwrap();
 }
 
-impl AppleStyle {
-    fn next(self) -> AppleStyle {
-        match self {
-            AppleStyle::Filled => AppleStyle::Outline,
-            AppleStyle::Outline => AppleStyle::Block,
-            AppleStyle::Block => AppleStyle::Ascii,
-            AppleStyle::Ascii => AppleStyle::Filled,
-        }
+// The board from a level file, position code or campaign stage, if there is
+// one, and the size it asked for, which may be too big
+fn load_board(
+    cli: &Cli,
+    mut builder: GameStateBuilder,
+    stage: usize,
+) -> Result<(GameStateBuilder, Option<(u16, u16)>), String> {
+    let mut requested = None;
+    if cli.campaign {
+        requested = Some(campaign::level(stage).board);
+        builder = builder.stage(stage, cli.campaign_apples);
+    }
+    if let Some(path) = &cli.level {
+        let level = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| Level::parse(&text).map_err(|e| e.to_string()))
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+        requested = Some(level.board);
+        builder = builder.level(level);
+    }
+    if let Some(code) = &cli.position {
+        let position = Position::decode(code).map_err(|e| format!("Can't load position: {e}"))?;
+        requested = Some(position.board);
+        builder = builder.position(position);
     }
+    Ok((builder, requested))
+}
 
-    fn display(self) -> char {
-        match self {
-            AppleStyle::Filled => '●',
-            AppleStyle::Outline => '○',
-            AppleStyle::Block => '█',
-            AppleStyle::Ascii => 'O',
-        }
+// The settings `previous` was changed to while it was played, for the next
+// game. Its board size is only kept with `board`, since a level or
+// position's board is part of it
+fn carry_over(builder: GameStateBuilder, previous: &GameState, board: bool) -> GameStateBuilder {
+    let builder = builder
+        .snake_style(previous.snake_style)
+        .apple_style(previous.apple_style)
+        .color(previous.color)
+        .square(previous.square)
+        .colorblind(previous.colorblind)
+        .rulers(previous.rulers)
+        .edges(previous.edges);
+    if board {
+        builder.board(previous.board.0, previous.board.1)
+    } else {
+        builder
     }
 }
 
-impl SegmentType {
-    fn display(self, style: SnakeStyle) -> char {
-        match style {
-            SnakeStyle::CurvedLine => match self {
-                SegmentType::NorthSouth => '│',
-                SegmentType::NorthEast => '╰',
-                SegmentType::NorthWest => '╯',
-                SegmentType::SouthEast => '╭',
-                SegmentType::SouthWest => '╮',
-                SegmentType::EastWest => '─',
-            },
-            SnakeStyle::SharpLine => match self {
-                SegmentType::NorthSouth => '│',
-                SegmentType::NorthEast => '└',
-                SegmentType::NorthWest => '┘',
-                SegmentType::SouthEast => '┌',
-                SegmentType::SouthWest => '┐',
-                SegmentType::EastWest => '─',
-            },
-            SnakeStyle::Ascii => match self {
-                SegmentType::NorthSouth => '|',
-                SegmentType::NorthEast => '`',
-                SegmentType::NorthWest => '`',
-                SegmentType::SouthEast => '.',
-                SegmentType::SouthWest => '.',
-                SegmentType::EastWest => '-',
-            },
-            SnakeStyle::Block => '█', // All segments are blocks
-        }
+// The game asked for on the command line, or why it can't be played
+// Playing again after `previous` keeps the styles, speed and board size it
+// was changed to while it was played. In a campaign, `stage` is the one to
+// play, which sets its own speed, and carries on the score from `previous`
+// past the first
+fn build_game(cli: &Cli, previous: Option<&GameState>, stage: usize) -> Result<GameState, String> {
+    let scroll = cli
+        .scroll
+        .then(terminal::size)
+        .transpose()
+        .map_err(|e| format!("Can't scroll without the terminal's size: {e}"))?;
+    let mut builder = GameStateBuilder::new()
+        .wrap_blocked(cli.wrap_blocked)
+        .colorblind(cli.colorblind)
+        .square(cli.square)
+        .compass(cli.compass)
+        .apple_timeout(cli.apple_timeout)
+        .time_limit(cli.time_limit.map(Duration::from_secs))
+        .magnet_rate(cli.magnet_rate)
+        .shrink_every(cli.shrink_every.map(Duration::from_secs))
+        .speed_bonus(cli.speed_bonus)
+        .blur(cli.blur)
+        .idle_pause(
+            cli.idle_pause
+                .or(cli.kiosk.map(|_| ATTRACT_AFTER))
+                .map(Duration::from_secs),
+        )
+        .attract(cli.kiosk.is_some() && !cli.autopilot)
+        .hardcore(cli.hardcore)
+        .eat_flash(cli.eat_flash)
+        .ghost_rate(cli.ghost_rate)
+        .swarm(cli.swarm)
+        .rival_skill(cli.rival_skill)
+        .tron(cli.tron)
+        .immediate_turns(cli.immediate_turns)
+        .show_length(cli.show_length)
+        .rewinds(cli.rewinds, cli.rewind_depth)
+        .time_attack(cli.time_attack)
+        .streak(cli.streak_boost, cli.streak_decay)
+        .speed_up(cli.speed_up, cli.speed_up_every, cli.speed_floor)
+        .shrink_policy(cli.shrink)
+        .fatal_blocks(cli.fatal_blocks)
+        .wall_grace(cli.wall_grace)
+        .breadcrumbs(cli.breadcrumbs)
+        .reversed(cli.reversed)
+        .mirror_rate(cli.mirror_rate)
+        .milestones(cli.milestone.clone())
+        .spawn_region(cli.spawn_region)
+        .shake(cli.shake)
+        .rulers(cli.rulers)
+        .ripple(cli.ripple, cli.ripple_delay)
+        .accept_paste(cli.accept_paste)
+        .autopilot(cli.autopilot)
+        .turn_queue(cli.turn_queue, cli.queue_overflow)
+        .lookahead(cli.lookahead)
+        .apple_inset(cli.apple_inset)
+        .banners(&cli.title, &cli.footer)
+        .wrap_arrows(cli.wrap_arrows)
+        .shield(cli.shield)
+        .borders(cli.border)
+        .keys(cli.keys)
+        .board(cli.width, cli.height)
+        .delay(cli.speed)
+        .snake_style(cli.snake_style)
+        .apple_style(cli.apple_style)
+        .color(!cli.no_color)
+        .obstacles(cli.obstacles)
+        .obstacle_gap(cli.obstacle_gap)
+        .scroll(scroll)
+        .fit_terminal(cli.fit_terminal.then(terminal::size).and_then(Result::ok));
+    if let Some(seed) = cli.seed {
+        builder = builder.seed(seed);
+    }
+    if cli.daily {
+        builder = builder.seed(daily::seed_for(&daily::today())).daily(true);
+    }
+    let (mut builder, loaded) = load_board(cli, builder, stage)?;
+    // The size asked for, which may be too big
+    let requested = loaded.unwrap_or((cli.width, cli.height));
+    if cli.wall_wrap {
+        builder = builder.edges(Edges::all(Edge::Wrap));
+    }
+    if let Some(edges) = cli.edges {
+        builder = builder.edges(edges);
     }
+    if let Some((x, y)) = cli.apple {
+        builder = builder.apple(x, y);
+    }
+    if let Some(previous) = previous {
+        builder = carry_over(builder, previous, loaded.is_none());
+    }
+    let mut game = builder
+        .build()
+        .map_err(|e| format!("Can't start the game: {e}"))?;
+    // Score multipliers still compare with the speed it started at
+    match previous {
+        Some(previous) if cli.campaign && stage > 0 => game.score = previous.score,
+        Some(_) if cli.campaign => {}
+        Some(previous) => game.delay = previous.delay,
+        None => {}
+    }
+    // Only the first game is told, any after it start from the board that
+    // one was cut down to
+    if previous.is_none() {
+        warn_cut(requested, &game);
+    }
+    Ok(game)
 }
 
-#[derive(Debug, Clone, Copy)]
-struct Segment(u16, u16, SegmentType, Direction);
-
-#[derive(Debug, Clone)]
-struct GameState {
-    snake: VecDeque<Segment>,
-    delay: Duration,
-    apple: (u16, u16),
-    head: (u16, u16),
-    board: (u16, u16),
-    direction: Direction,
-    snake_style: SnakeStyle,
-    apple_style: AppleStyle,
-    wall_wrap: bool,
-    color: bool,
+// Say so when the board asked for was too big and has been cut down
+fn warn_cut((width, height): (u16, u16), game: &GameState) {
+    if game.fit_terminal.is_none() && (width, height) != game.board {
+        let (max_width, max_height) = MAX_BOARD;
+        eprintln!(
+            "The board is {width}x{height}, cutting it down to at most \
+             {max_width}x{max_height}"
+        );
+    }
 }
 
-impl GameState {
-    fn new() -> Self {
-        GameState {
-            snake: {
-                let mut v = VecDeque::new();
-                v.push_back(Segment(0, 0, SegmentType::EastWest, Direction::East));
-                v.push_back(Segment(1, 0, SegmentType::EastWest, Direction::East));
-                v
-            },
-            delay: Duration::from_millis(250),
-            apple: (5, 5),
-            head: (1, 0),
-            board: (10, 10),
-            direction: Direction::East,
-            snake_style: SnakeStyle::CurvedLine,
-            apple_style: AppleStyle::Filled,
-            wall_wrap: false,
-            color: true,
-        }
+// Log a finished game, returning where it couldn't be saved and why, since
+// failing to is only worth a warning. A high score gets a name when someone's
+// there to type one, and the leaderboard it's on is shown under the game
+fn log_game(cli: &Cli, finished: &GameState, tty: bool) -> Option<String> {
+    let path = cli.stats.clone().or_else(stats::default_path)?;
+    let mut records = stats::load(&path).unwrap_or_default();
+    let mut record = GameRecord::from_game(finished);
+    let drawn = tty && !cli.announce;
+    if drawn && cli.kiosk.is_none() && stats::high_score(&records, &record) {
+        record.name = ask_name();
     }
+    let error = stats::append(&path, &record)
+        .err()
+        .map(|e| format!("{}: {e}", path.display()));
+    records.push(record);
+    if drawn {
+        high_scores(&records);
+    }
+    error
 }
 
-fn render_all(game: &GameState) {
-    // Clear
-    execute!(stdout(), Clear(ClearType::All)).unwrap();
+// Save the settings a game ended with to the config file, returning where
+// they couldn't be saved and why. The board size is only kept when it wasn't
+// set by a level, position, campaign or the terminal
+fn remember(cli: &Cli, path: &Path, config: &mut Config, finished: &GameState) -> Option<String> {
+    let board = cli.level.is_none() && cli.position.is_none() && !cli.fit_terminal && !cli.campaign;
+    config.update_from(finished, board);
+    config::save(path, config)
+        .err()
+        .map(|e| format!("{}: {e}", path.display()))
+}
 
-    // Apple
-    execute!(stdout(), MoveTo(game.apple.0, game.apple.1)).unwrap();
-    if game.color {
-        print!("{}", style(game.apple_style.display()).with(Color::Red));
+// Play a game to the end, drawn on the terminal or described in text
+fn play(
+    cli: &Cli,
+    game: GameState,
+    recorder: Option<Recorder<BufWriter<File>>>,
+    tty: bool,
+) -> (GameResult, GameState) {
+    if cli.announce || !tty {
+        let announcer = Announcer::new(stdout());
+        run_game(game, CrosstermInput, sink(), &mut (announcer, recorder))
     } else {
-        print!("{}", game.apple_style.display());
+        let out = BufWriter::with_capacity(FRAME_BUFFER, stdout().lock());
+        run_game(game, CrosstermInput, out, &mut ((), recorder))
     }
+}
 
-    // Snake
-    for Segment(x, y, s, _) in &game.snake {
-        execute!(stdout(), MoveTo(*x, *y)).unwrap();
-        if game.color {
-            print!("{}", style(s.display(game.snake_style)).with(Color::Green));
-        } else {
-            print!("{}", s.display(game.snake_style))
+// Whether r is pressed after a game to play another, anything else quits
+fn play_again() -> bool {
+    discard_pending();
+    print!("\r\n\r\nr to play again, any other key to quit");
+    stdout().flush().unwrap();
+    while let Ok(event) = event::read() {
+        if let Event::Key(k) = event {
+            return k.code == KeyCode::Char('r');
         }
     }
+    false
+}
 
-    // Board
-    execute!(stdout(), MoveTo(0, game.board.1)).unwrap();
+// Whether a key asks to stop: q, Esc or Ctrl-C
+fn stop_key(k: &KeyEvent) -> bool {
+    let ctrl_c = k.code == KeyCode::Char('c') && k.modifiers.contains(KeyModifiers::CONTROL);
+    ctrl_c || matches!(k.code, KeyCode::Char('q') | KeyCode::Esc)
+}
+
+// Between campaign stages, which level is next and the score so far, until
+// it starts after STAGE_PAUSE or a key is pressed
+// Returns false if q, Esc or Ctrl-C is pressed to stop instead
+fn level_up(stage: usize, finished: &GameState) -> bool {
+    discard_pending();
+    execute!(stdout(), Clear(ClearType::All), MoveTo(0, 0)).unwrap();
     print!(
-        "{}",
-        SegmentType::EastWest
-            .display(game.snake_style)
-            .to_string()
-            .repeat(game.board.0 as usize)
+        "Level {} of {}\r\n\r\nScore: {}\r\n\r\nStarting in {}s, any key to start now, q to quit",
+        stage + 1,
+        campaign::stages(),
+        finished.score,
+        STAGE_PAUSE.as_secs()
     );
-    for i in 0..game.board.1 {
-        execute!(stdout(), MoveTo(game.board.0, i)).unwrap();
-        print!(
-            "{}",
-            SegmentType::NorthSouth
-                .display(game.snake_style)
-                .to_string()
-        );
+    stdout().flush().unwrap();
+
+    let deadline = Instant::now() + STAGE_PAUSE;
+    while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+        if let Ok(Some(Event::Key(k))) = CrosstermInput.poll(left) {
+            return !stop_key(&k);
+        }
     }
-    execute!(stdout(), MoveTo(game.board.0, game.board.1)).unwrap();
+    true
+}
+
+// Between kiosk games, the session so far until the next game in `delay`
+// Returns false if q, Esc or Ctrl-C is pressed to stop instead
+fn interlude(session: &Session, delay: Duration) -> bool {
+    discard_pending();
     print!(
-        "{}",
-        SegmentType::NorthWest.display(game.snake_style).to_string()
+        "\r\n\r\n{session}\r\nNext game in {}s, q to quit",
+        delay.as_secs()
     );
+    stdout().flush().unwrap();
 
-    // Flush
-    stdout().lock().flush().unwrap();
+    let deadline = Instant::now() + delay;
+    while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+        if let Ok(Some(Event::Key(k))) = CrosstermInput.poll(left) {
+            if stop_key(&k) {
+                return false;
+            }
+        }
+    }
+    true
 }
 
-fn game_over() {
-    println!("\nGame Over");
+// Put the terminal back how the game found it
+fn restore_terminal(tty: bool) {
+    if tty {
+        let _ = execute!(stdout(), Show, DisableBracketedPaste);
+    }
+    let _ = disable_raw_mode();
 }
 
-fn handle_input(game: &Arc<Mutex<GameState>>) {
-    if let Event::Key(k) = read().unwrap() {
-        let mut game = game.lock().unwrap();
-        match k.code {
-            // Quit
-            KeyCode::Char('q') => {
-                execute!(stdout(), Show).unwrap();
-                disable_raw_mode().unwrap();
-                println!();
-                std::process::exit(0);
-            }
-
-            // Up
-            KeyCode::Char('k') | KeyCode::Up => {
-                if game.snake[game.snake.len() - 1].3 != Direction::South {
-                    game.direction = Direction::North;
-                }
-            }
-
-            // Down
-            KeyCode::Char('j') | KeyCode::Down => {
-                if game.snake[game.snake.len() - 1].3 != Direction::North {
-                    game.direction = Direction::South;
-                }
-            }
+// Raw mode, and the cursor hidden when drawing, for as long as it's held.
+// The terminal is restored when it's dropped, when anything panics, even off
+// the main thread, and when the game is killed by a signal
+struct TerminalGuard {
+    tty: bool,
+}
 
-            // Left
-            KeyCode::Char('h') | KeyCode::Left => {
-                if game.snake[game.snake.len() - 1].3 != Direction::East {
-                    game.direction = Direction::West;
-                }
-            }
+impl TerminalGuard {
+    fn new(tty: bool, paste: bool) -> io::Result<Self> {
+        enable_raw_mode()?;
+        let guard = TerminalGuard { tty };
+        if tty {
+            execute!(stdout(), Hide)?;
+        }
+        // Otherwise a paste arrives as key presses, which the game acts on
+        if tty && paste {
+            execute!(stdout(), EnableBracketedPaste)?;
+        }
 
-            // Right
-            KeyCode::Char('l') | KeyCode::Right => {
-                if game.snake[game.snake.len() - 1].3 != Direction::West {
-                    game.direction = Direction::East;
-                }
-            }
+        // Restored before the message, which raw mode would scramble
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore_terminal(tty);
+            hook(info);
+        }));
+        watch_signals(tty);
+        Ok(guard)
+    }
+}
 
-            // Decrease board x
-            KeyCode::Char('1') => {
-                game.board.0 = game.board.0.checked_sub(1).unwrap();
-                render_all(&game);
-            }
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal(self.tty);
+    }
+}
 
-            // Increase board x
-            KeyCode::Char('2') => {
-                game.board.0 = game.board.0.checked_add(1).unwrap();
-                render_all(&game);
-            }
+// Nothing is dropped when a signal ends the game, so restore the terminal
+// before going. Ctrl-C only sends one before raw mode is on, after that it's
+// a key like any other
+#[cfg(unix)]
+fn watch_signals(tty: bool) {
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+    use signal_hook::iterator::Signals;
 
-            // Decrease board y
-            KeyCode::Char('3') => {
-                game.board.1 = game.board.1.checked_sub(1).unwrap();
-                render_all(&game);
-            }
+    let Ok(mut signals) = Signals::new([SIGHUP, SIGINT, SIGTERM]) else {
+        return;
+    };
+    thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            restore_terminal(tty);
+            std::process::exit(128 + signal);
+        }
+    });
+}
 
-            // Increase board x
-            KeyCode::Char('4') => {
-                game.board.1 = game.board.1.checked_add(1).unwrap();
-                render_all(&game);
-            }
+#[cfg(not(unix))]
+fn watch_signals(_tty: bool) {}
 
-            // Decrease speed
-            KeyCode::Char('5') => {
-                game.delay = game.delay.checked_add(Duration::from_millis(20)).unwrap();
-            }
+// The frames of a recording, exiting if it can't be played back
+fn load_replay(path: &Path) -> Vec<Frame> {
+    let frames = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| replay::parse(&text).map_err(|e| e.to_string()));
+    frames.unwrap_or_else(|e| {
+        eprintln!("{}: {e}", path.display());
+        std::process::exit(1);
+    })
+}
 
-            // Increase speed
-            KeyCode::Char('6') => {
-                game.delay = game.delay.checked_sub(Duration::from_millis(20)).unwrap();
-            }
+// Read the config file, giving its settings to those left off the command
+// line, and exiting if it can't be read
+fn load_config(cli: &mut Cli, matches: &ArgMatches) -> (Option<PathBuf>, Config) {
+    let path = cli.config.clone().or_else(config::default_path);
+    let config = match path.as_deref().map(config::load).transpose() {
+        Ok(config) => config.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("{}: {e}", path.unwrap_or_default().display());
+            std::process::exit(1);
+        }
+    };
 
-            // Cycle snake style
-            KeyCode::Char('7') => {
-                game.snake_style = game.snake_style.next();
-                render_all(&game);
-            }
+    let unset = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+    if let Some(width) = config.width.filter(|_| unset("width")) {
+        cli.width = width;
+    }
+    if let Some(height) = config.height.filter(|_| unset("height")) {
+        cli.height = height;
+    }
+    if let Some(delay) = config.delay.filter(|_| unset("speed")) {
+        cli.speed = delay;
+    }
+    if let Some(style) = config.snake_style.filter(|_| unset("snake_style")) {
+        cli.snake_style = style;
+    }
+    if let Some(style) = config.apple_style.filter(|_| unset("apple_style")) {
+        cli.apple_style = style;
+    }
+    if let Some(wrap) = config.wall_wrap.filter(|_| unset("wall_wrap")) {
+        cli.wall_wrap = wrap;
+    }
+    if let Some(color) = config.color.filter(|_| unset("no_color")) {
+        cli.no_color = !color;
+    }
+    cli.keys = config.keys.keys();
+    (path, config)
+}
 
-            // Cycle apple style
-            KeyCode::Char('8') => {
-                game.apple_style = game.apple_style.next();
-                render_all(&game);
-            }
+// Print the version, and where settings are read from, for bug reports
+// This is done here rather than by clap, once the config path is known
+fn print_version(cli: &Cli) {
+    let config = match cli.config.clone().or_else(config::default_path) {
+        Some(path) if path.exists() => path.display().to_string(),
+        Some(path) => format!("{} (not found, using the defaults)", path.display()),
+        None => "none".to_string(),
+    };
+    let version = format!("{}\nconfig: {config}", env!("CARGO_PKG_VERSION"));
+    print!(
+        "{}",
+        Cli::command().long_version(version).render_long_version()
+    );
+}
 
-            // Toggle wall wrapping (The snake lives on a torus !!)
-            KeyCode::Char('9') => {
-                game.wall_wrap = !game.wall_wrap;
-            }
+fn main() {
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
 
-            // Toggle color
-            KeyCode::Char('0') => {
-                game.color = !game.color;
-                render_all(&game);
-            }
+    if cli.version {
+        print_version(&cli);
+        return;
+    }
 
-            _ => {}
+    if let Some(mode) = cli.leaderboard {
+        match cli.stats.clone().or_else(stats::default_path) {
+            Some(path) => show_leaderboard(&path, mode),
+            None => eprintln!("No stats file, pass one with --stats"),
         }
+        return;
     }
-}
 
-fn main() {
-    enable_raw_mode().unwrap();
-    execute!(stdout(), Hide).unwrap();
+    let (config_path, mut config) = load_config(&mut cli, &matches);
+    let mut game = build_game(&cli, None, 0).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
 
-    let game = Arc::new(Mutex::new(GameState::new()));
+    let frames = cli.replay.as_deref().map(load_replay);
+    let recorder = cli.record.as_ref().map(|path| {
+        File::create(path)
+            .and_then(|file| Recorder::new(BufWriter::new(file), game.seed))
+            .unwrap_or_else(|e| {
+                eprintln!("Can't record to {}: {e}", path.display());
+                std::process::exit(1);
+            })
+    });
 
-    // Spawn input loop in another thread
-    {
-        let game = Arc::clone(&game);
+    // Cursor movement would end up as garbage in a file or pipe, so describe
+    // the game in plain text there instead
+    let tty = stdout().is_terminal();
 
-        thread::spawn(move || loop {
-            handle_input(&game);
-        });
-    };
+    if frames.is_some() && !tty {
+        eprintln!("Replays can only be played back in a terminal");
+        std::process::exit(1);
+    }
 
-    let mut rng = thread_rng();
+    // Keys are still read from the terminal, if there is one
+    let terminal = TerminalGuard::new(tty, cli.accept_paste).unwrap_or_else(|e| {
+        eprintln!("Can't read keys without a terminal: {e}");
+        std::process::exit(1);
+    });
 
-    // Game loop
-    loop {
-        let (head, board, direction, wall_wrap) = {
-            let game = game.lock().unwrap();
-            (game.head, game.board, game.direction, game.wall_wrap)
-        };
+    let mut session = Session::default();
+    let mut stats_error = None;
+    let mut config_error = None;
+    // Why the session ended early, once the terminal's in raw mode a game
+    // that can't be played or played back ends the session rather than the
+    // program
+    let mut next_error = None;
+    if let Some(frames) = &frames {
+        let mut out = BufWriter::with_capacity(FRAME_BUFFER, stdout().lock());
+        if let Err(e) = replay::play(&mut game, frames, &mut out) {
+            next_error = Some(format!("Playback stopped: {e}"));
+        }
+    } else {
+        let mut recorder = recorder;
+        loop {
+            let (result, finished) = play(&cli, game, recorder.take(), tty);
 
-        // New head position, based on direction
-        // Wraps if collides with wall and wall_wrap is true
-        // Exits loop if collides with wall and wall_wrap is false
-        let new_head = match direction {
-            Direction::North => {
-                if head.1 > 0 {
-                    (head.0, head.1 - 1)
-                } else if wall_wrap {
-                    (head.0, board.1 - 1)
-                } else {
-                    break;
-                }
+            // A cleared campaign stage carries on into the next, if there is one
+            let next_stage = finished.stage.map(|stage| stage + 1).filter(|&stage| {
+                matches!(result, GameResult::Cleared) && stage < campaign::stages()
+            });
+
+            // Only finished games count towards the session, and games the
+            // snake played by itself aren't logged
+            let over = next_stage.is_none() && !matches!(result, GameResult::Quit);
+            if over {
+                session.record(&finished);
             }
-            Direction::South => {
-                if head.1 + 1 < board.1 {
-                    (head.0, head.1 + 1)
-                } else if wall_wrap {
-                    (head.0, 0)
-                } else {
-                    break;
-                }
+
+            if over && !finished.autopilot {
+                stats_error = log_game(&cli, &finished, tty);
             }
-            Direction::West => {
-                if head.0 > 0 {
-                    (head.0 - 1, head.1)
-                } else if wall_wrap {
-                    (board.0 - 1, head.1)
-                } else {
-                    break;
-                }
+
+            // Keep the settings it ended with for next time, if asked to
+            if let Some(path) = config_path.as_deref().filter(|_| config.remember) {
+                config_error = remember(&cli, path, &mut config, &finished);
             }
-            Direction::East => {
-                if head.0 + 1 < board.0 {
-                    (head.0 + 1, head.1)
-                } else if wall_wrap {
-                    (0, head.1)
-                } else {
+
+            let next = match (next_stage, cli.kiosk) {
+                (Some(stage), _) if level_up(stage, &finished) => {
+                    build_game(&cli, Some(&finished), stage)
+                }
+                // A key pressed during a demo starts a game for whoever pressed it
+                (None, Some(_)) if finished.attracting => build_game(&cli, None, 0),
+                (None, Some(delay)) if over && interlude(&session, Duration::from_secs(delay)) => {
+                    build_game(&cli, None, 0)
+                }
+                (None, None) if over && play_again() => build_game(&cli, Some(&finished), 0),
+                _ => break,
+            };
+            match next {
+                Ok(next) => game = next,
+                Err(e) => {
+                    next_error = Some(e);
                     break;
                 }
             }
-        };
-        let delay = {
-            let mut game = game.lock().unwrap();
-
-            // Snake contains new position, self-collision
-            if game.snake.iter().any(|x| (x.0, x.1) == new_head) {
-                break;
-            }
-            // Set head
-            game.head = new_head;
-
-            // Update second-to-last segment
-            let len = game.snake.len();
-            game.snake[len - 1].2 =
-                SegmentType::from_next(game.snake[game.snake.len() - 1].3, game.direction);
-
-            // New head segment
-            let segment = Segment(
-                new_head.0,
-                new_head.1,
-                SegmentType::from_dir(game.direction),
-                game.direction,
-            );
-
-            // Remove oldest segment, unless you ate an apple
-            if new_head == game.apple {
-                // New apple position
-                game.apple = (
-                    rng.gen_range(0..game.board.0),
-                    rng.gen_range(0..game.board.1),
-                );
-            } else {
-                // Remove oldest segment
-                game.snake.pop_front();
-            }
-
-            // Add new head segment
-            game.snake.push_back(segment);
-
-            // Render
-            render_all(&game);
-            game.delay
-        };
-        thread::sleep(delay);
+        }
     }
 
-    // Loop will end when game over
-
-    // Render snake about to die
-    {
-        let mut game = game.lock().unwrap();
-        let len = game.snake.len();
-        game.snake[len - 1].2 = SegmentType::from_next(game.snake[len - 1].3, game.direction);
-        render_all(&game);
+    // Single exit point, restores the terminal once the last frame and
+    // summary are all out
+    if let Err(e) = stdout().flush() {
+        eprintln!("Couldn't finish drawing the game: {e}");
     }
-
-    game_over();
-
-    execute!(stdout(), Show).unwrap();
-    disable_raw_mode().unwrap();
+    drop(terminal);
     println!();
+    if session.games > 0 {
+        println!("{session}");
+    }
+
+    if let Some(e) = stats_error {
+        eprintln!("Couldn't save stats to {e}");
+    }
+    if let Some(e) = config_error {
+        eprintln!("Couldn't save settings to {e}");
+    }
+    if let Some(e) = next_error {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
 }
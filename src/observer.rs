@@ -0,0 +1,189 @@
+// Hooks for watching a game as it's played
+
+use std::io::Write;
+
+use crate::game::{Direction, GameResult, GameState};
+
+/// Watches a game from the game loop's thread
+///
+/// Both methods default to doing nothing, and `()` is the observer that
+/// watches nothing.
+pub trait GameObserver {
+    /// Called after every tick that moved the snake
+    fn tick(&mut self, _game: &GameState) {}
+
+    /// Called once when the game ends
+    fn finish(&mut self, _game: &GameState, _result: GameResult) {}
+}
+
+impl GameObserver for () {}
+
+// Two observers watch the same game, the first hearing of each tick first
+impl<A: GameObserver, B: GameObserver> GameObserver for (A, B) {
+    fn tick(&mut self, game: &GameState) {
+        self.0.tick(game);
+        self.1.tick(game);
+    }
+
+    fn finish(&mut self, game: &GameState, result: GameResult) {
+        self.0.finish(game, result);
+        self.1.finish(game, result);
+    }
+}
+
+// An observer that might not be there
+impl<O: GameObserver> GameObserver for Option<O> {
+    fn tick(&mut self, game: &GameState) {
+        if let Some(observer) = self {
+            observer.tick(game);
+        }
+    }
+
+    fn finish(&mut self, game: &GameState, result: GameResult) {
+        if let Some(observer) = self {
+            observer.finish(game, result);
+        }
+    }
+}
+
+/// One tick of a game, as handed out by [`Events`]
+///
+/// There's one event for every tick the snake moves, and one more when the
+/// game ends. The fields are:
+///
+/// - `tick`: counts the events from 1, so the end has the last number
+/// - `direction`: the way the snake moved, or last moved at the end
+/// - `head`: where the head is now, column then row from 0 at the top left
+/// - `outcome`: what came of the move, see [`Outcome`]
+/// - `score`: the score after the tick
+#[derive(Debug, Clone, Copy)]
+pub struct TickEvent {
+    pub tick: u64,
+    pub direction: Direction,
+    pub head: (u16, u16),
+    pub outcome: Outcome,
+    pub score: u32,
+}
+
+/// What came of a tick
+#[derive(Debug, Clone, Copy)]
+pub enum Outcome {
+    /// The snake moved onto an empty cell
+    Moved,
+    /// The snake ate at least one apple
+    Ate,
+    /// The game is over, the last event there is
+    Ended(GameResult),
+}
+
+/// Hands each tick to `send` as a [`TickEvent`], for a program watching the
+/// game live, say to train or judge a bot
+///
+/// Nothing is worked out unless one of these is watching.
+pub struct Events<F: FnMut(TickEvent)> {
+    send: F,
+    tick: u64,
+    apples: u32,
+}
+
+impl<F: FnMut(TickEvent)> Events<F> {
+    #[must_use]
+    pub fn new(send: F) -> Self {
+        Events {
+            send,
+            tick: 0,
+            apples: 0,
+        }
+    }
+
+    fn send(&mut self, game: &GameState, outcome: Outcome) {
+        self.tick += 1;
+        (self.send)(TickEvent {
+            tick: self.tick,
+            direction: game.snake[game.snake.len() - 1].3,
+            head: game.head,
+            outcome,
+            score: game.score,
+        });
+    }
+}
+
+impl<F: FnMut(TickEvent)> GameObserver for Events<F> {
+    fn tick(&mut self, game: &GameState) {
+        let outcome = if game.apples > self.apples {
+            Outcome::Ate
+        } else {
+            Outcome::Moved
+        };
+        self.apples = game.apples;
+        self.send(game, outcome);
+    }
+
+    fn finish(&mut self, game: &GameState, result: GameResult) {
+        self.send(game, Outcome::Ended(result));
+    }
+}
+
+/// Describes the game in plain lines of text, for screen readers
+///
+/// Only meaningful changes are announced: the apple moving, the snake
+/// turning or growing, and the end of the game. Lines that can't be written
+/// are dropped, a failing announcer doesn't end the game.
+pub struct Announcer<W: Write> {
+    out: W,
+    last: Option<((u16, u16), Direction, usize)>,
+}
+
+impl<W: Write> Announcer<W> {
+    #[must_use]
+    pub fn new(out: W) -> Self {
+        Announcer { out, last: None }
+    }
+
+    // Lines end in \r\n, the terminal is still in raw mode for input
+    fn say(&mut self, line: &str) {
+        write!(self.out, "{line}\r\n").ok();
+        self.out.flush().ok();
+    }
+}
+
+impl<W: Write> GameObserver for Announcer<W> {
+    fn tick(&mut self, game: &GameState) {
+        let direction = game.snake[game.snake.len() - 1].3;
+        let now = (game.apple, direction, game.snake.len());
+        if self.last == Some(now) {
+            return;
+        }
+        self.last = Some(now);
+
+        let heading = match direction {
+            Direction::North => "north",
+            Direction::South => "south",
+            Direction::East => "east",
+            Direction::West => "west",
+        };
+        self.say(&format!(
+            "apple at column {} row {}, head at {},{}, heading {heading}, length {}",
+            game.apple.0,
+            game.apple.1,
+            game.head.0,
+            game.head.1,
+            game.snake.len()
+        ));
+    }
+
+    fn finish(&mut self, game: &GameState, result: GameResult) {
+        let ending = match result {
+            GameResult::Quit => "quit",
+            GameResult::Died(_) => "game over",
+            GameResult::TimeUp => "time's up",
+            GameResult::Won => "you win",
+            GameResult::Filled => "board filled, you win",
+            GameResult::Cleared => "level cleared",
+        };
+        self.say(&format!(
+            "{ending}, score {}, seed {}",
+            game.score, game.seed
+        ));
+    }
+}
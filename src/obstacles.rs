@@ -0,0 +1,119 @@
+// Randomly placed obstacles
+
+use std::collections::{HashSet, VecDeque};
+
+use rand::prelude::*;
+
+use crate::game::GameState;
+
+// Add up to `count` walls in random places, as many as fit while keeping
+// them more than `gap` cells (including diagonally) from each other and from
+// the snake and apple, and every empty cell reachable from every other
+pub(crate) fn generate(game: &mut GameState, count: usize, gap: u16, rng: &mut impl Rng) {
+    let (width, height) = game.board;
+    let mut cells: Vec<(u16, u16)> = (0..width)
+        .flat_map(|x| (0..height).map(move |y| (x, y)))
+        .collect();
+    cells.shuffle(rng);
+
+    let near = |a: (u16, u16), b: (u16, u16)| a.0.abs_diff(b.0) <= gap && a.1.abs_diff(b.1) <= gap;
+    let mut placed = Vec::new();
+    for cell in cells {
+        if placed.len() == count {
+            break;
+        }
+        let crowded = placed.iter().any(|&wall| near(wall, cell))
+            || game.snake.iter().any(|s| near((s.0, s.1), cell))
+            || near(game.apple, cell)
+            || game.walls.contains(&cell)
+            || game.blocks.contains(&cell);
+        if crowded {
+            continue;
+        }
+
+        game.walls.insert(cell);
+        if connected(game) {
+            placed.push(cell);
+        } else {
+            game.walls.remove(&cell);
+        }
+    }
+}
+
+// Whether every cell that isn't a wall can be reached from the head
+fn connected(game: &GameState) -> bool {
+    let (width, height) = game.board;
+    let mut seen = HashSet::from([game.head]);
+    let mut queue = VecDeque::from([game.head]);
+    while let Some((x, y)) = queue.pop_front() {
+        let neighbours = [
+            x.checked_sub(1).map(|x| (x, y)),
+            (x + 1 < width).then_some((x + 1, y)),
+            y.checked_sub(1).map(|y| (x, y)),
+            (y + 1 < height).then_some((x, y + 1)),
+        ];
+        for cell in neighbours.into_iter().flatten() {
+            if !game.walls.contains(&cell) && seen.insert(cell) {
+                queue.push_back(cell);
+            }
+        }
+    }
+    seen.len() + game.walls.len() == usize::from(width) * usize::from(height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::GameStateBuilder;
+    use rand::rngs::StdRng;
+
+    // A 20 by 20 board with `count` obstacles scattered on it from `seed`
+    fn scattered(seed: u64, count: usize, gap: u16) -> GameState {
+        let mut game = GameStateBuilder::new()
+            .seed(seed)
+            .board(20, 20)
+            .build()
+            .unwrap();
+        game.apple = (10, 10);
+        generate(&mut game, count, gap, &mut StdRng::seed_from_u64(seed));
+        game
+    }
+
+    #[test]
+    fn obstacles_keep_their_distance() {
+        for seed in 0..10 {
+            let game = scattered(seed, 15, 2);
+            assert!(!game.walls.is_empty());
+            let far = |a: (u16, u16), b: (u16, u16)| a.0.abs_diff(b.0) > 2 || a.1.abs_diff(b.1) > 2;
+            for &wall in &game.walls {
+                let others = game.walls.iter().filter(|&&other| other != wall);
+                assert!(others.copied().all(|other| far(wall, other)));
+                assert!(game.snake.iter().all(|s| far(wall, (s.0, s.1))));
+                assert!(far(wall, game.apple));
+            }
+        }
+    }
+
+    #[test]
+    fn board_stays_connected() {
+        for seed in 0..10 {
+            let game = scattered(seed, 40, 0);
+            assert_eq!(game.walls.len(), 40);
+            assert!(connected(&game));
+            assert!(!game.walls.contains(&game.apple));
+            assert!(game.snake.iter().all(|s| !game.walls.contains(&(s.0, s.1))));
+        }
+    }
+
+    #[test]
+    fn as_many_obstacles_as_fit() {
+        let game = scattered(1, 1000, 0);
+        assert!(game.walls.len() < 400);
+        assert!(connected(&game));
+    }
+
+    #[test]
+    fn same_seed_same_obstacles() {
+        assert_eq!(scattered(7, 30, 1).walls, scattered(7, 30, 1).walls);
+    }
+}
@@ -0,0 +1,454 @@
+// Short codes for sharing a position
+//
+// A code is the URL-safe base64 (no padding) of these bytes, numbers are
+// little endian u16s, apart from the two counts, which are u32s:
+//
+//   version
+//   board width, height
+//   apple x, y
+//   tail x, y
+//   snake length, then the direction of each move from the tail to the head,
+//     two bits each, four to a byte
+//   wall count, then each wall's x, y
+//
+// Version 1 codes, with u16 counts, still decode.
+//
+// Moves step off one edge of the board onto the other, so wrapped snakes
+// come back as they were, unless they wrapped to a custom row or column.
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+
+use crate::game::{Direction, GameState, Segment, SegmentType, MAX_BOARD};
+
+const VERSION: u8 = 2;
+
+// The first version, with u16 counts
+const VERSION_1: u8 = 1;
+
+// The longest code the biggest board can give, with every cell a wall
+// Anything longer is rejected before decoding
+const MAX_CELLS: usize = MAX_BOARD.0 as usize * MAX_BOARD.1 as usize;
+const MAX_BYTES: usize = 1 + 6 * 2 + 4 + MAX_CELLS.div_ceil(4) + 4 + MAX_CELLS * 4;
+const MAX_CODE_LEN: usize = MAX_BYTES.div_ceil(3) * 4;
+
+/// The board, walls, snake and apple of a game
+#[derive(Debug, Clone)]
+pub struct Position {
+    pub board: (u16, u16),
+    pub walls: HashSet<(u16, u16)>,
+    pub snake: VecDeque<Segment>,
+    pub apple: (u16, u16),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PositionError {
+    TooLong,
+    TooBig,
+    NotBase64,
+    UnknownVersion(u8),
+    Truncated,
+    TrailingBytes,
+    EmptyBoard,
+    SnakeTooShort,
+    OffBoard,
+    Overlap,
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PositionError::TooLong => write!(f, "code is too long"),
+            PositionError::TooBig => write!(f, "the snake or walls are too big to share"),
+            PositionError::NotBase64 => write!(f, "code isn't valid base64"),
+            PositionError::UnknownVersion(v) => write!(f, "unknown code version {v}"),
+            PositionError::Truncated => write!(f, "code is cut short"),
+            PositionError::TrailingBytes => write!(f, "code has extra data at the end"),
+            PositionError::EmptyBoard => write!(f, "the board has no cells"),
+            PositionError::SnakeTooShort => write!(f, "the snake needs at least two segments"),
+            PositionError::OffBoard => write!(f, "the apple or a wall is off the board"),
+            PositionError::Overlap => {
+                write!(f, "the snake, apple and walls can't share a cell")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+// Reads the fields back out of a decoded code
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8], PositionError> {
+        if self.bytes.len() < n {
+            return Err(PositionError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, PositionError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, PositionError> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, PositionError> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    // A count, which version 1 codes keep in a u16
+    fn count(&mut self, version: u8) -> Result<usize, PositionError> {
+        let count = if version == VERSION_1 {
+            u32::from(self.u16()?)
+        } else {
+            self.u32()?
+        };
+        usize::try_from(count).map_err(|_| PositionError::TooLong)
+    }
+
+    fn cell(&mut self) -> Result<(u16, u16), PositionError> {
+        Ok((self.u16()?, self.u16()?))
+    }
+}
+
+fn direction_bits(direction: Direction) -> u8 {
+    match direction {
+        Direction::North => 0,
+        Direction::South => 1,
+        Direction::East => 2,
+        Direction::West => 3,
+    }
+}
+
+fn bits_direction(bits: u8) -> Direction {
+    match bits & 3 {
+        0 => Direction::North,
+        1 => Direction::South,
+        2 => Direction::East,
+        _ => Direction::West,
+    }
+}
+
+// One step in a direction, coming back round the other side of the board
+fn step((x, y): (u16, u16), (width, height): (u16, u16), direction: Direction) -> (u16, u16) {
+    match direction {
+        Direction::North => (x, y.checked_sub(1).unwrap_or(height - 1)),
+        Direction::South => (x, (y + 1) % height),
+        Direction::East => ((x + 1) % width, y),
+        Direction::West => (x.checked_sub(1).unwrap_or(width - 1), y),
+    }
+}
+
+impl Position {
+    /// The position a game is in
+    #[must_use]
+    pub fn from_game(game: &GameState) -> Position {
+        Position {
+            board: game.board,
+            walls: game.walls.clone(),
+            snake: game.snake.clone(),
+            apple: game.apple,
+        }
+    }
+
+    /// Encode as a short code, the same position always gives the same code
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PositionError::TooBig`] if the snake or walls have more
+    /// than `u32::MAX` cells, which no board allowed by `MAX_BOARD` has.
+    pub fn encode(&self) -> Result<String, PositionError> {
+        let count = |n: usize| u32::try_from(n).map_err(|_| PositionError::TooBig);
+        let mut bytes = vec![VERSION];
+        let mut push = |n: u16| bytes.extend_from_slice(&n.to_le_bytes());
+        push(self.board.0);
+        push(self.board.1);
+        push(self.apple.0);
+        push(self.apple.1);
+        push(self.snake[0].0);
+        push(self.snake[0].1);
+        bytes.extend_from_slice(&count(self.snake.len())?.to_le_bytes());
+
+        let moves: Vec<u8> = self
+            .snake
+            .iter()
+            .skip(1)
+            .map(|s| direction_bits(s.3))
+            .collect();
+        for chunk in moves.chunks(4) {
+            bytes.push(
+                chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0, |byte, (i, bits)| byte | bits << (2 * i)),
+            );
+        }
+
+        let mut walls: Vec<_> = self.walls.iter().copied().collect();
+        walls.sort_unstable();
+        bytes.extend_from_slice(&count(walls.len())?.to_le_bytes());
+        for (x, y) in walls {
+            bytes.extend_from_slice(&x.to_le_bytes());
+            bytes.extend_from_slice(&y.to_le_bytes());
+        }
+
+        Ok(URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    /// Decode a code made by [`Position::encode`]
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`PositionError`] if the code is malformed, too long, or
+    /// describes a position that can't be played.
+    pub fn decode(code: &str) -> Result<Position, PositionError> {
+        let code = code.trim();
+        if code.len() > MAX_CODE_LEN {
+            return Err(PositionError::TooLong);
+        }
+        let bytes = URL_SAFE_NO_PAD
+            .decode(code)
+            .map_err(|_| PositionError::NotBase64)?;
+        let mut r = Reader { bytes: &bytes };
+
+        let version = r.u8()?;
+        if version != VERSION && version != VERSION_1 {
+            return Err(PositionError::UnknownVersion(version));
+        }
+        let board = r.cell()?;
+        if board.0 == 0 || board.1 == 0 {
+            return Err(PositionError::EmptyBoard);
+        }
+        let on_board = |(x, y): (u16, u16)| x < board.0 && y < board.1;
+        let apple = r.cell()?;
+        let tail = r.cell()?;
+        let length = r.count(version)?;
+        if length < 2 {
+            return Err(PositionError::SnakeTooShort);
+        }
+        if !on_board(apple) || !on_board(tail) {
+            return Err(PositionError::OffBoard);
+        }
+        if tail == apple {
+            return Err(PositionError::Overlap);
+        }
+
+        // Walk the snake from its tail, every cell must be new
+        let moves = r.take((length - 1).div_ceil(4))?;
+        let mut cells = HashSet::from([tail, apple]);
+        let mut path = vec![(tail, Direction::East)];
+        for i in 0..length - 1 {
+            let direction = bits_direction(moves[i / 4] >> (2 * (i % 4)));
+            let cell = step(path[path.len() - 1].0, board, direction);
+            if !cells.insert(cell) {
+                return Err(PositionError::Overlap);
+            }
+            path.push((cell, direction));
+        }
+        path[0].1 = path[1].1;
+
+        let mut walls = HashSet::new();
+        for _ in 0..r.count(version)? {
+            let wall = r.cell()?;
+            if !on_board(wall) {
+                return Err(PositionError::OffBoard);
+            }
+            if cells.contains(&wall) || !walls.insert(wall) {
+                return Err(PositionError::Overlap);
+            }
+        }
+        if !r.bytes.is_empty() {
+            return Err(PositionError::TrailingBytes);
+        }
+
+        // Bends go where the next segment heads off in a new direction
+        let snake = path
+            .iter()
+            .enumerate()
+            .map(|(i, &((x, y), direction))| {
+                let segment = match path.get(i + 1) {
+                    Some(&(_, next)) if i > 0 => SegmentType::from_next(direction, next),
+                    Some(&(_, next)) => SegmentType::from_dir(next),
+                    None => SegmentType::from_dir(direction),
+                };
+                Segment(x, y, segment, direction)
+            })
+            .collect();
+
+        Ok(Position {
+            board,
+            walls,
+            snake,
+            apple,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A position with the snake along `cells`, tail first
+    fn position(
+        board: (u16, u16),
+        cells: &[((u16, u16), Direction)],
+        apple: (u16, u16),
+    ) -> Position {
+        let snake = cells
+            .iter()
+            .map(|&((x, y), d)| Segment(x, y, SegmentType::from_dir(d), d))
+            .collect();
+        Position {
+            board,
+            walls: HashSet::new(),
+            snake,
+            apple,
+        }
+    }
+
+    fn cells(position: &Position) -> Vec<(u16, u16, Direction)> {
+        position.snake.iter().map(|s| (s.0, s.1, s.3)).collect()
+    }
+
+    fn round_trip(position: &Position) -> Position {
+        let code = position.encode().unwrap();
+        let decoded = Position::decode(&code).unwrap();
+        assert_eq!(decoded.board, position.board);
+        assert_eq!(decoded.apple, position.apple);
+        assert_eq!(decoded.walls, position.walls);
+        assert_eq!(cells(&decoded), cells(position));
+        assert_eq!(decoded.encode().unwrap(), code);
+        decoded
+    }
+
+    #[test]
+    fn bent_snake_and_walls_round_trip() {
+        let mut p = position(
+            (5, 5),
+            &[
+                ((1, 1), Direction::East),
+                ((2, 1), Direction::East),
+                ((2, 2), Direction::South),
+                ((1, 2), Direction::West),
+            ],
+            (3, 3),
+        );
+        p.walls = HashSet::from([(0, 0), (4, 4), (0, 4)]);
+        let decoded = round_trip(&p);
+        let bend = SegmentType::from_next(Direction::East, Direction::South);
+        assert_eq!(decoded.snake[1].2, bend);
+    }
+
+    #[test]
+    fn wrapped_snake_round_trips() {
+        let p = position(
+            (4, 3),
+            &[
+                ((3, 1), Direction::East),
+                ((0, 1), Direction::East),
+                ((1, 1), Direction::East),
+            ],
+            (2, 2),
+        );
+        round_trip(&p);
+    }
+
+    #[test]
+    fn more_walls_than_a_u16_round_trip() {
+        let mut p = position(
+            MAX_BOARD,
+            &[((0, 0), Direction::East), ((1, 0), Direction::East)],
+            (2, 0),
+        );
+        p.walls = (0..70_000u32)
+            .map(|i| {
+                (
+                    u16::try_from(i % 1000).unwrap(),
+                    u16::try_from(i / 1000 + 1).unwrap(),
+                )
+            })
+            .collect();
+        let code = p.encode().unwrap();
+        assert!(code.len() <= MAX_CODE_LEN);
+        assert_eq!(Position::decode(&code).unwrap().walls.len(), 70_000);
+    }
+
+    #[test]
+    fn version_one_codes_decode() {
+        let mut bytes = vec![VERSION_1];
+        for n in [5u16, 5, 3, 3, 1, 1, 3] {
+            bytes.extend_from_slice(&n.to_le_bytes());
+        }
+        // East, then South
+        bytes.push(2 | 1 << 2);
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes.extend_from_slice(&4u16.to_le_bytes());
+
+        let decoded = Position::decode(&URL_SAFE_NO_PAD.encode(bytes)).unwrap();
+        assert_eq!(
+            cells(&decoded),
+            [
+                (1, 1, Direction::East),
+                (2, 1, Direction::East),
+                (2, 2, Direction::South),
+            ]
+        );
+        assert_eq!(decoded.walls, HashSet::from([(0, 4)]));
+    }
+
+    fn code(version: u8, length: u32, moves: &[u8], extra: &[u8]) -> String {
+        let mut bytes = vec![version];
+        for n in [5u16, 5, 3, 3, 1, 1] {
+            bytes.extend_from_slice(&n.to_le_bytes());
+        }
+        bytes.extend_from_slice(&length.to_le_bytes());
+        bytes.extend_from_slice(moves);
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(extra);
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    #[test]
+    fn bad_codes_are_rejected() {
+        let decode = |code: &str| Position::decode(code).unwrap_err();
+        assert_eq!(
+            decode(&code(VERSION, 1, &[], &[])),
+            PositionError::SnakeTooShort
+        );
+        assert_eq!(
+            decode(&code(9, 2, &[2], &[])),
+            PositionError::UnknownVersion(9)
+        );
+        assert_eq!(
+            decode(&code(VERSION, 2, &[2], &[0])),
+            PositionError::TrailingBytes
+        );
+        // East, South, West, North comes back to the tail
+        let square = 2 | 1 << 2 | 3 << 4;
+        assert_eq!(
+            decode(&code(VERSION, 5, &[square], &[])),
+            PositionError::Overlap
+        );
+        let valid = code(VERSION, 2, &[2], &[]);
+        assert!(Position::decode(&valid).is_ok());
+        assert_eq!(decode(&valid[..valid.len() - 4]), PositionError::Truncated);
+        assert_eq!(decode("not base64!"), PositionError::NotBase64);
+        assert_eq!(
+            decode(&"A".repeat(MAX_CODE_LEN + 1)),
+            PositionError::TooLong
+        );
+    }
+}
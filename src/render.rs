@@ -0,0 +1,868 @@
+// Drawing the game to a terminal
+
+use std::io::{self, Write};
+
+use crossterm::cursor::MoveTo;
+use crossterm::queue;
+use crossterm::style::Color;
+
+use crate::campaign;
+use crate::game::{Borders, Direction, Edge, GameState, Segment, SegmentType, SnakeStyle};
+use crate::position::Position;
+use crate::screen::{Canvas, Screen};
+
+// The part of the board on screen, which is all of it unless scrolling
+struct View {
+    origin: (u16, u16),
+    size: (u16, u16),
+    // Terminal columns per cell, square cells are two wide
+    cell_width: u16,
+    // Terminal columns and rows left of and above the board, for the title,
+    // rulers and border
+    offset: (u16, u16),
+    // Rows under the prompt taken by the footer
+    footer: u16,
+    borders: Borders,
+}
+
+impl View {
+    fn new(game: &GameState) -> View {
+        let cell_width = if game.square { 2 } else { 1 };
+
+        // The row ruler is as wide as the last row's number
+        let mut offset = if game.rulers {
+            let digits = (game.board.1 - 1).to_string().len();
+            (u16::try_from(digits).unwrap(), 1)
+        } else {
+            (0, 0)
+        };
+        offset.0 += u16::from(game.borders.left);
+        offset.1 += u16::from(!game.title.is_empty()) + u16::from(game.borders.top);
+        let footer = u16::from(!game.footer.is_empty());
+
+        let mut view = View {
+            origin: (0, 0),
+            size: game.board,
+            cell_width,
+            offset,
+            footer,
+            borders: game.borders,
+        };
+
+        // Scrolling keeps the head in the middle of whatever fits on the
+        // terminal
+        let Some(terminal) = game.scroll else {
+            return view;
+        };
+        let room = view.room(terminal);
+        let size = (game.board.0.min(room.0), game.board.1.min(room.1));
+        view.origin = (
+            game.head
+                .0
+                .saturating_sub(size.0 / 2)
+                .min(game.board.0 - size.0),
+            game.head
+                .1
+                .saturating_sub(size.1 / 2)
+                .min(game.board.1 - size.1),
+        );
+        view.size = size;
+        view
+    }
+
+    // Cells that fit on a terminal this size, leaving room for the border,
+    // rulers, HUD and prompt
+    fn room(&self, (columns, rows): (u16, u16)) -> (u16, u16) {
+        let (right, bottom) = (
+            u16::from(self.borders.right),
+            u16::from(self.borders.bottom),
+        );
+        (
+            (columns.saturating_sub(right + self.offset.0) / self.cell_width).max(1),
+            rows.saturating_sub(2 + bottom + self.offset.1 + self.footer)
+                .max(1),
+        )
+    }
+
+    // Terminal position of a board cell, None if it's off screen
+    fn screen(&self, (x, y): (u16, u16)) -> Option<(u16, u16)> {
+        let x = x.checked_sub(self.origin.0).filter(|&x| x < self.size.0)?;
+        let y = y.checked_sub(self.origin.1).filter(|&y| y < self.size.1)?;
+        Some((self.offset.0 + x * self.cell_width, self.offset.1 + y))
+    }
+
+    // The HUD stays put just below the view, whatever it's scrolled to
+    fn hud_row(&self) -> u16 {
+        self.offset.1 + self.size.1 + u16::from(self.borders.bottom)
+    }
+
+    // First row free for text once the frame's drawn, under the prompt and
+    // footer
+    fn end_row(&self) -> u16 {
+        self.hud_row() + 2 + self.footer
+    }
+
+    // Terminal columns the board takes up, with its rulers and border
+    fn width(&self) -> u16 {
+        self.offset.0 + self.size.0 * self.cell_width + u16::from(self.borders.right)
+    }
+}
+
+// Draw a board cell if it's on screen, `fill` goes in its second column when
+// cells are square
+fn draw_cell(
+    canvas: &mut Canvas,
+    game: &GameState,
+    view: &View,
+    cell: (u16, u16),
+    (glyph, fill): (char, char),
+    color: Color,
+) {
+    let Some((x, y)) = view.screen(cell) else {
+        return;
+    };
+    let color = game.color.then_some(color);
+    canvas.put((x, y), glyph, color);
+    if game.square {
+        canvas.put((x + 1, y), fill, color);
+    }
+}
+
+// Arrow pointing from the head towards the apple, and how many moves away it is
+fn compass(head: (u16, u16), apple: (u16, u16)) -> String {
+    let dx = i32::from(apple.0) - i32::from(head.0);
+    let dy = i32::from(apple.1) - i32::from(head.1);
+
+    // Within a factor of two of each other counts as diagonal
+    let horizontal = dx.abs() > 2 * dy.abs();
+    let vertical = dy.abs() > 2 * dx.abs();
+    let arrow = match (dx.signum(), dy.signum()) {
+        (0, 0) => '·',
+        (x, _) if horizontal => {
+            if x > 0 {
+                '→'
+            } else {
+                '←'
+            }
+        }
+        (_, y) if vertical => {
+            if y > 0 {
+                '↓'
+            } else {
+                '↑'
+            }
+        }
+        (1, 1) => '↘',
+        (1, _) => '↗',
+        (_, 1) => '↙',
+        _ => '↖',
+    };
+    format!("{arrow} {}", dx.abs() + dy.abs())
+}
+
+// Glyph for the border along an edge of the board, the top or bottom if
+// it's `across`, dashed when the snake wraps through it
+fn border(game: &GameState, edge: Edge, across: bool) -> char {
+    let style = game.snake_style;
+    match (edge == Edge::Wall, across, style) {
+        (true, true, _) => SegmentType::EastWest.display(style),
+        (true, false, _) => SegmentType::NorthSouth.display(style),
+        (false, true, SnakeStyle::CurvedLine | SnakeStyle::SharpLine) => '┄',
+        (false, false, SnakeStyle::CurvedLine | SnakeStyle::SharpLine) => '┆',
+        (false, _, SnakeStyle::Block) => '░',
+        (false, true, SnakeStyle::Ascii) => '.',
+        (false, false, SnakeStyle::Ascii) => ':',
+    }
+}
+
+fn arrow(direction: Direction, style: SnakeStyle) -> char {
+    match (style, direction) {
+        (SnakeStyle::Ascii, Direction::North) => '^',
+        (SnakeStyle::Ascii, Direction::South) => 'v',
+        (SnakeStyle::Ascii, Direction::East) => '>',
+        (SnakeStyle::Ascii, Direction::West) => '<',
+        (_, Direction::North) => '↑',
+        (_, Direction::South) => '↓',
+        (_, Direction::East) => '→',
+        (_, Direction::West) => '←',
+    }
+}
+
+// The arrow a ghost apple is drawn as
+fn ghost_arrow(game: &GameState) -> Option<char> {
+    game.ghost
+        .map(|direction| arrow(direction, game.snake_style))
+}
+
+// The arrow a segment is drawn as when it's where the snake leaves or comes
+// back in through a wrapping edge, pointing the way it went so the snake
+// doesn't look cut in two. Only straight segments, bends already show it
+fn wrap_arrow(game: &GameState, index: usize) -> Option<char> {
+    if !game.wrap_arrows {
+        return None;
+    }
+    let snake = &game.snake;
+    let apart = |a: &Segment, b: &Segment| a.0.abs_diff(b.0) + a.1.abs_diff(b.1) != 1;
+    let segment = &snake[index];
+    let leaves = snake
+        .get(index + 1)
+        .filter(|next| apart(segment, next))
+        .map(|next| next.3);
+    let comes_in = index
+        .checked_sub(1)
+        .filter(|&i| apart(&snake[i], segment))
+        .map(|_| segment.3);
+    let direction = leaves.or(comes_in)?;
+    (segment.2 == SegmentType::from_dir(direction)).then(|| arrow(direction, game.snake_style))
+}
+
+// The snake's length next to its head, on free cells to the right, left,
+// above or below, whichever comes first, or not at all if none are free
+fn length_tag(canvas: &mut Canvas, game: &GameState, view: &View) {
+    let text = game.snake.len().to_string();
+    let cells = u16::try_from(text.len()).unwrap().div_ceil(view.cell_width);
+    let (x, y) = game.head;
+    let starts = [
+        x.checked_add(1).map(|x| (x, y)),
+        x.checked_sub(cells).map(|x| (x, y)),
+        y.checked_sub(1).map(|y| (x, y)),
+        y.checked_add(1).map(|y| (x, y)),
+    ];
+    let fits = |&(x, y): &(u16, u16)| {
+        (x..x.saturating_add(cells)).all(|x| {
+            x < game.board.0
+                && y < game.board.1
+                && game.is_free((x, y))
+                && view.screen((x, y)).is_some()
+        })
+    };
+    if let Some(at) = starts
+        .into_iter()
+        .flatten()
+        .find(fits)
+        .and_then(|cell| view.screen(cell))
+    {
+        canvas.text(at, &text, game.color.then_some(Color::Grey));
+    }
+}
+
+// Where the apple has been, fading as it ages, drawn before anything else on
+// the board so it never covers it
+fn breadcrumbs(canvas: &mut Canvas, game: &GameState, view: &View) {
+    for &(cell, left) in &game.breadcrumbs {
+        let fresh = 2 * left > game.breadcrumb_ticks;
+        let glyph = match (fresh, game.snake_style) {
+            (true, SnakeStyle::Ascii) => ':',
+            (false, SnakeStyle::Ascii) => '.',
+            (true, _) => '•',
+            (false, _) => '·',
+        };
+        let color = if fresh { Color::Grey } else { Color::DarkGrey };
+        draw_cell(canvas, game, view, cell, (glyph, ' '), color);
+    }
+}
+
+// Whether a cell outside the board's shape is next to the board, including
+// diagonally, so it's drawn as part of its outline
+fn outlines(game: &GameState, (x, y): (u16, u16)) -> bool {
+    (x.saturating_sub(1)..=x.saturating_add(1)).any(|nx| {
+        (y.saturating_sub(1)..=y.saturating_add(1))
+            .any(|ny| nx < game.board.0 && ny < game.board.1 && !game.void.contains(&(nx, ny)))
+    })
+}
+
+// Walls, and blocks, and the outline of the board when it isn't a rectangle
+fn walls(canvas: &mut Canvas, game: &GameState, view: &View) {
+    let wall = match game.snake_style {
+        SnakeStyle::Ascii => '#',
+        _ => '▒',
+    };
+    for &cell in &game.walls {
+        if !game.void.contains(&cell) || outlines(game, cell) {
+            draw_cell(canvas, game, view, cell, (wall, wall), Color::DarkGrey);
+        }
+    }
+
+    // Blocks, which the snake can push
+    let block = match game.snake_style {
+        SnakeStyle::Ascii => 'o',
+        _ => '■',
+    };
+    for &cell in &game.blocks {
+        draw_cell(canvas, game, view, cell, (block, ' '), Color::DarkYellow);
+    }
+}
+
+// The level's zones, tinting whatever's left empty once everything else is
+// drawn over them, only in color
+fn zones(canvas: &mut Canvas, game: &GameState, view: &View) {
+    if !game.color {
+        return;
+    }
+    for zone in &game.zones {
+        for x in zone.origin.0..zone.origin.0.saturating_add(zone.size.0) {
+            for y in zone.origin.1..zone.origin.1.saturating_add(zone.size.1) {
+                if let Some((x, y)) = view.screen((x, y)) {
+                    for column in x..x + view.cell_width {
+                        canvas.tint((column, y), zone.color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// The board's borders, on the sides asked for and only those in view when
+// scrolling
+fn edges(canvas: &mut Canvas, game: &GameState, view: &View) {
+    let (width, height) = view.size;
+    let (left, top) = view.offset;
+    let right = left + width * view.cell_width;
+    let below = top + height;
+    let borders = game.borders;
+    let top_edge = borders.top && view.origin.1 == 0;
+    let left_edge = borders.left && view.origin.0 == 0;
+    let bottom_edge = borders.bottom && view.origin.1 + height == game.board.1;
+    let right_edge = borders.right && view.origin.0 + width == game.board.0;
+    for x in left..right {
+        if top_edge {
+            canvas.put((x, top - 1), border(game, game.edges.north, true), None);
+        }
+        if bottom_edge {
+            canvas.put((x, below), border(game, game.edges.south, true), None);
+        }
+    }
+    for y in top..below {
+        if left_edge {
+            canvas.put((left - 1, y), border(game, game.edges.west, false), None);
+        }
+        if right_edge {
+            canvas.put((right, y), border(game, game.edges.east, false), None);
+        }
+    }
+
+    let corners = [
+        (
+            top_edge && left_edge,
+            (left.wrapping_sub(1), top.wrapping_sub(1)),
+            SegmentType::SouthEast,
+        ),
+        (
+            top_edge && right_edge,
+            (right, top.wrapping_sub(1)),
+            SegmentType::SouthWest,
+        ),
+        (
+            bottom_edge && left_edge,
+            (left.wrapping_sub(1), below),
+            SegmentType::NorthEast,
+        ),
+        (
+            bottom_edge && right_edge,
+            (right, below),
+            SegmentType::NorthWest,
+        ),
+    ];
+    for (drawn, at, corner) in corners {
+        if drawn {
+            canvas.put(at, corner.display(game.snake_style), None);
+        }
+    }
+}
+
+// Column numbers above the board, the last digit of each, and row numbers
+// down its left, only for the part in view
+fn rulers(canvas: &mut Canvas, game: &GameState, view: &View) {
+    let color = game.color.then_some(Color::DarkGrey);
+    let row = view.offset.1 - 1 - u16::from(game.borders.top);
+    for x in view.origin.0..view.origin.0 + view.size.0 {
+        if let Some((column, _)) = view.screen((x, view.origin.1)) {
+            let digit = char::from_digit(u32::from(x % 10), 10).unwrap();
+            canvas.put((column, row), digit, color);
+        }
+    }
+    let digits = usize::from(view.offset.0 - u16::from(game.borders.left));
+    for y in view.origin.1..view.origin.1 + view.size.1 {
+        if let Some((_, row)) = view.screen((view.origin.0, y)) {
+            canvas.text((0, row), &format!("{y:>digits$}"), color);
+        }
+    }
+}
+
+// Grow or shrink the board to fill the terminal it's fitted to, resizing by
+// so many cells with `resize`, which decides what to do about cutting into
+// anything on it
+pub(crate) fn fit_board(game: &mut GameState, mut resize: impl FnMut(&mut GameState, i16, i16)) {
+    let Some(terminal) = game.fit_terminal else {
+        return;
+    };
+    // The row ruler widens with the board, so measure again once it's resized
+    for _ in 0..2 {
+        let room = View::new(game).room(terminal);
+        let step = |want: u16, have: u16| {
+            i16::try_from(i32::from(want) - i32::from(have)).unwrap_or(i16::MAX)
+        };
+        resize(game, step(room.0, game.board.0), step(room.1, game.board.1));
+    }
+}
+
+pub(crate) fn render_all(screen: &mut Screen<impl Write>, game: &GameState) -> io::Result<()> {
+    screen.present(compose(game, None))
+}
+
+// Draw a replay frame, with a line about playback on the prompt row
+pub(crate) fn render_replay(
+    screen: &mut Screen<impl Write>,
+    game: &GameState,
+    status: &str,
+) -> io::Result<()> {
+    screen.present(compose(game, Some(status)))
+}
+
+// The rival snakes, joined up across the gap between square cells
+fn rivals(canvas: &mut Canvas, game: &GameState, view: &View) {
+    let east_west = SegmentType::EastWest.display(game.snake_style);
+    for rival in &game.rivals {
+        for Segment(x, y, s, _) in &rival.body {
+            let fill = if s.joins_east() { east_west } else { ' ' };
+            let glyph = s.display(game.snake_style);
+            draw_cell(canvas, game, view, (*x, *y), (glyph, fill), rival.color);
+        }
+    }
+}
+
+// Dots where the head's heading, under everything else on the board
+// Returns the cell it would crash on, to be marked over the top at the end
+fn lookahead(canvas: &mut Canvas, game: &GameState, view: &View) -> Option<(u16, u16)> {
+    let projection = game.projection();
+    let glyph = match game.snake_style {
+        SnakeStyle::Ascii => '.',
+        _ => '·',
+    };
+    for cell in projection.path {
+        draw_cell(canvas, game, view, cell, (glyph, ' '), Color::DarkCyan);
+    }
+    projection.crash
+}
+
+// A line of text centered over the board on `row`, cut short if it's wider
+fn banner(canvas: &mut Canvas, game: &GameState, view: &View, text: &str, row: u16) {
+    let width = match game.scroll {
+        Some((columns, _)) => view.width().min(columns),
+        None => view.width(),
+    };
+    let text: String = text.chars().take(usize::from(width)).collect();
+    let length = u16::try_from(text.chars().count()).unwrap();
+    let color = game.color.then_some(Color::Cyan);
+    canvas.text(((width - length) / 2, row), &text, color);
+}
+
+// Written across the middle of the board while the game is paused, which
+// stays in view around it
+fn paused(canvas: &mut Canvas, game: &GameState, view: &View) {
+    let width = view.size.0 * view.cell_width;
+    let text: String = " PAUSED ".chars().take(usize::from(width)).collect();
+    let length = u16::try_from(text.chars().count()).unwrap();
+    let at = (
+        view.offset.0 + (width - length) / 2,
+        view.offset.1 + view.size.1 / 2,
+    );
+    canvas.text(at, &text, game.color.then_some(Color::Yellow));
+}
+
+// The apple, and the rivals' apples, none of which there are in tron mode
+fn apples(canvas: &mut Canvas, game: &GameState, view: &View, apple: char, color: Color) {
+    if game.tron {
+        return;
+    }
+
+    // Ghost apples show the way they have to be entered
+    let apple = ghost_arrow(game).unwrap_or(apple);
+    draw_cell(canvas, game, view, game.apple, (apple, ' '), color);
+
+    // The rivals' apples, never ghosts
+    for &cell in &game.spare_apples {
+        let spare = if game.colorblind {
+            '◆'
+        } else {
+            game.apple_style.display()
+        };
+        draw_cell(canvas, game, view, cell, (spare, ' '), color);
+    }
+}
+
+// Everything on screen for a frame, `status` goes on the prompt row when
+// there's no prompt
+fn compose(game: &GameState, status: Option<&str>) -> Canvas {
+    let mut canvas = Canvas::default();
+    let view = View::new(game);
+    zones(&mut canvas, game, &view);
+    breadcrumbs(&mut canvas, game, &view);
+    let crash = lookahead(&mut canvas, game, &view);
+
+    // Colorblind mode tells snake and apple apart by brightness and shape,
+    // not just red versus green
+    let (snake_color, afterimage_color, apple_color, apple) = if game.colorblind {
+        (Color::Blue, Color::DarkBlue, Color::Yellow, '◆')
+    } else {
+        (
+            Color::Green,
+            Color::DarkGreen,
+            Color::Red,
+            game.apple_style.display(),
+        )
+    };
+
+    // The snake changes color while a streak speeds it up, and shimmers
+    // while it's safe after eating
+    let snake_color = match (game.boost > 0.0, game.colorblind) {
+        _ if game.shield % 2 == 1 => Color::White,
+        (false, _) => snake_color,
+        (true, false) => Color::Yellow,
+        (true, true) => Color::Cyan,
+    };
+
+    // The afterimage goes under anything that's since moved onto its cell
+    let east_west = SegmentType::EastWest.display(game.snake_style);
+    if let Some(Segment(x, y, s, _)) = game.afterimage {
+        let fill = if s.joins_east() { east_west } else { ' ' };
+        let glyph = s.display(game.snake_style);
+        draw_cell(
+            &mut canvas,
+            game,
+            &view,
+            (x, y),
+            (glyph, fill),
+            afterimage_color,
+        );
+    }
+
+    apples(&mut canvas, game, &view, apple, apple_color);
+
+    // Apple magnet and mirror power-ups
+    if let Some(cell) = game.magnet {
+        draw_cell(&mut canvas, game, &view, cell, ('U', ' '), Color::Magenta);
+    }
+    if let Some(cell) = game.mirror {
+        draw_cell(&mut canvas, game, &view, cell, ('R', ' '), Color::Cyan);
+    }
+
+    walls(&mut canvas, game, &view);
+
+    rivals(&mut canvas, game, &view);
+
+    // Snake, joined up across the gap between square cells
+    for (index, Segment(x, y, s, _)) in game.snake.iter().enumerate() {
+        let fill = if s.joins_east() { east_west } else { ' ' };
+        let flash = game.flash > 0 && (*x, *y) == game.head;
+        let rippled = game.rippled_over(index);
+
+        // Without color, the flash and ripple are glyphs instead
+        let glyph = match (flash, rippled, game.color) {
+            (_, true, false) => 'x',
+            (true, _, false) => '+',
+            _ => wrap_arrow(game, index).unwrap_or_else(|| s.display(game.snake_style)),
+        };
+        let color = if rippled {
+            Color::Red
+        } else if flash {
+            Color::White
+        } else {
+            snake_color
+        };
+        draw_cell(&mut canvas, game, &view, (*x, *y), (glyph, fill), color);
+    }
+    if game.show_length {
+        length_tag(&mut canvas, game, &view);
+    }
+
+    // Marked over whatever the snake would crash into
+    if let Some(cell) = crash {
+        draw_cell(&mut canvas, game, &view, cell, ('X', ' '), Color::Red);
+    }
+
+    edges(&mut canvas, game, &view);
+    if game.paused.is_some() {
+        paused(&mut canvas, game, &view);
+    }
+    if game.rulers {
+        rulers(&mut canvas, game, &view);
+    }
+    if !game.title.is_empty() {
+        banner(&mut canvas, game, &view, &game.title, 0);
+    }
+    if !game.footer.is_empty() {
+        banner(&mut canvas, game, &view, &game.footer, view.hud_row() + 2);
+    }
+
+    hud(
+        &mut canvas,
+        game,
+        &view,
+        game.color.then_some(apple_color),
+        status,
+    );
+    // Only the scrolling view knows how big the terminal is, otherwise the
+    // board is taken to fit with room to spare
+    canvas.shift(
+        game.shake_offset(),
+        game.scroll.unwrap_or((u16::MAX, u16::MAX)),
+    );
+
+    canvas
+}
+
+// The HUD below the board, and the prompt under it
+fn hud(
+    canvas: &mut Canvas,
+    game: &GameState,
+    view: &View,
+    apple_color: Option<Color>,
+    status: Option<&str>,
+) {
+    // HUD, how the game's going first and then whatever else is on
+    let mut x = 0;
+    let hud = view.hud_row();
+    let progress = format!(
+        "Score: {}  Length: {}  Delay: {}ms  ",
+        game.score,
+        game.snake.len(),
+        game.tick_delay().as_millis()
+    );
+    x = canvas.text((x, hud), &progress, None);
+    if let (Some(stage), Some(goal)) = (game.stage, game.apple_goal) {
+        let level = format!(
+            "Level {}/{}  Apples: {}/{goal}  ",
+            stage + 1,
+            campaign::stages(),
+            game.apples
+        );
+        x = canvas.text((x, hud), &level, None);
+    }
+    if !game.speed_step.is_zero() {
+        x = canvas.text((x, hud), &format!("Level: {}  ", game.speed_level()), None);
+    }
+    if let Some(left) = game.time_left() {
+        x = canvas.text((x, hud), &format!("Time: {}s  ", left.as_secs()), None);
+    }
+    if game.speed_bonus > 0.0 {
+        let multiplier = format!("x{:.1}  ", game.speed_multiplier());
+        x = canvas.text((x, hud), &multiplier, None);
+    }
+    if game.rewinds > 0 {
+        x = canvas.text((x, hud), &format!("Rewinds: {}  ", game.rewinds), None);
+    }
+    if game.boost > 0.0 {
+        let streak = format!("Streak {} +{:.0}%  ", game.streak, game.boost * 100.0);
+        x = canvas.text((x, hud), &streak, None);
+    }
+    if let Some((length, bonus)) = game.milestone.filter(|_| game.cheer > 0) {
+        let cheer = format!("Length {length}! +{bonus}  ");
+        x = canvas.text((x, hud), &cheer, game.color.then_some(Color::Yellow));
+    }
+    if game.controls_reversed() {
+        x = canvas.text((x, hud), "REVERSED  ", game.color.then_some(Color::Cyan));
+    }
+
+    // Flashes while the snake waits at a wall
+    if game.grace.is_some_and(|left| left % 2 == 0) {
+        let warning = game.color.then_some(Color::Red);
+        x = canvas.text((x, hud), "Wall! Turn!  ", warning);
+    }
+    if game.compass {
+        let compass = compass(game.head, game.apple);
+        canvas.text((x, hud), &compass, apple_color);
+    }
+
+    // Prompts
+    let prompt = if game.confirm_quit {
+        Some("Quit? (y/n)".to_string())
+    } else if game.confirm_shrink.is_some() {
+        Some("Shrink into the snake? (y/n)".to_string())
+    } else if let Some((notice, _)) = game.notice {
+        Some(notice.to_string())
+    } else if game.paused.is_some() {
+        Some("Paused, p to carry on".to_string())
+    } else if game.idle {
+        Some("auto-paused (idle)".to_string())
+    } else if game.attracting {
+        Some("Demo, press any key to play".to_string())
+    } else if game.show_position {
+        match Position::from_game(game).encode() {
+            Ok(code) => Some(format!("Position: {code}")),
+            Err(e) => Some(format!("Can't share this position: {e}")),
+        }
+    } else {
+        status.map(str::to_string)
+    };
+    if let Some(prompt) = prompt {
+        canvas.text((0, hud + 1), &prompt, None);
+    }
+}
+
+// Written straight to the terminal under the last frame, nothing is drawn
+// after them. Each is a heading and the final score, then the seed, so a
+// good run can be played again or shared with `--seed`
+// Lines end in \r\n, the terminal is still in raw mode for input
+fn end_screen(
+    screen: &mut Screen<impl Write>,
+    game: &GameState,
+    heading: &str,
+    score: &str,
+) -> io::Result<()> {
+    let out = &mut screen.out;
+    queue!(out, MoveTo(0, View::new(game).end_row()))?;
+    write!(out, "{heading}\r\n{score}\r\nSeed: {}", game.seed)?;
+    out.flush()
+}
+
+pub(crate) fn game_over(screen: &mut Screen<impl Write>, game: &GameState) -> io::Result<()> {
+    let score = format!("Score: {}", game.score);
+    end_screen(screen, game, "Game Over", &score)
+}
+
+// Nothing more to say, but whatever's printed after the game goes under it
+pub(crate) fn quit(screen: &mut Screen<impl Write>, game: &GameState) -> io::Result<()> {
+    let out = &mut screen.out;
+    queue!(out, MoveTo(0, View::new(game).end_row()))?;
+    out.flush()
+}
+
+pub(crate) fn you_win(screen: &mut Screen<impl Write>, game: &GameState) -> io::Result<()> {
+    let score = format!("Score: {}", game.score);
+    end_screen(screen, game, "You outlasted them all!", &score)
+}
+
+// The snake's filled every cell it can, so show how it got there
+pub(crate) fn board_filled(screen: &mut Screen<impl Write>, game: &GameState) -> io::Result<()> {
+    let score = format!(
+        "Score: {}  Length: {}  Apples: {}  Time: {}s",
+        game.score,
+        game.snake.len(),
+        game.apples,
+        game.elapsed().as_secs()
+    );
+    end_screen(screen, game, "You Win! The snake fills the board", &score)
+}
+
+// A campaign stage is over, with the next one still to come unless it was
+// the last
+pub(crate) fn cleared(screen: &mut Screen<impl Write>, game: &GameState) -> io::Result<()> {
+    let last = game
+        .stage
+        .is_some_and(|stage| stage + 1 >= campaign::stages());
+    let heading = if last {
+        "You Win! Every level cleared"
+    } else {
+        "Level cleared!"
+    };
+    let score = format!("Score: {}", game.score);
+    end_screen(screen, game, heading, &score)
+}
+
+pub(crate) fn time_up(screen: &mut Screen<impl Write>, game: &GameState) -> io::Result<()> {
+    let score = format!("Score: {}", game.score);
+    end_screen(screen, game, "Time's up!", &score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::GameStateBuilder;
+    use crate::game::Direction;
+
+    // The top left of a frame, so many columns by so many rows
+    fn frame(game: &GameState, (columns, rows): (u16, u16)) -> Vec<String> {
+        let canvas = compose(game, None);
+        (0..rows)
+            .map(|y| (0..columns).map(|x| canvas.glyph((x, y))).collect())
+            .collect()
+    }
+
+    #[test]
+    fn snake_through_a_wrapping_edge_points_the_way_it_went() {
+        let mut game = GameStateBuilder::new()
+            .seed(1)
+            .board(6, 3)
+            .wrap(true)
+            .wrap_arrows(true)
+            .apple(3, 2)
+            .build()
+            .unwrap();
+        game.snake = [4, 5, 0, 1]
+            .into_iter()
+            .map(|x| Segment(x, 1, SegmentType::EastWest, Direction::East))
+            .collect();
+        game.head = (1, 1);
+        let drawn = frame(&game, (7, 4));
+        assert_eq!(drawn, ["      ┆", "→─  ─→┆", "   ●  ┆", "┄┄┄┄┄┄╯"]);
+    }
+
+    #[test]
+    fn borders_are_drawn_on_the_sides_asked_for() {
+        for sides in 0..16 {
+            let borders = Borders {
+                top: sides & 1 != 0,
+                bottom: sides & 2 != 0,
+                left: sides & 4 != 0,
+                right: sides & 8 != 0,
+            };
+            let game = GameStateBuilder::new()
+                .seed(1)
+                .board(3, 2)
+                .apple(2, 1)
+                .borders(borders)
+                .build()
+                .unwrap();
+
+            let row = |left: char, middle: &str, right: char| {
+                let mut line = String::new();
+                if borders.left {
+                    line.push(left);
+                }
+                line.push_str(middle);
+                if borders.right {
+                    line.push(right);
+                }
+                line
+            };
+            let mut expected = Vec::new();
+            if borders.top {
+                expected.push(row('╭', "───", '╮'));
+            }
+            expected.push(row('│', "── ", '│'));
+            expected.push(row('│', "  ●", '│'));
+            if borders.bottom {
+                expected.push(row('╰', "───", '╯'));
+            }
+
+            let columns = 3 + u16::from(borders.left) + u16::from(borders.right);
+            let rows = 2 + u16::from(borders.top) + u16::from(borders.bottom);
+            assert_eq!(frame(&game, (columns, rows)), expected, "{borders:?}");
+        }
+    }
+
+    #[test]
+    fn game_over_shows_the_seed() {
+        let game = GameStateBuilder::new().seed(8_675_309).build().unwrap();
+        let mut screen = Screen::new(Vec::new());
+        game_over(&mut screen, &game).unwrap();
+        assert!(String::from_utf8_lossy(&screen.out).contains("8675309"));
+    }
+
+    #[test]
+    fn end_screens_share_a_layout() {
+        type EndScreen = fn(&mut Screen<Vec<u8>>, &GameState) -> io::Result<()>;
+        let game = GameStateBuilder::new().seed(1).build().unwrap();
+        let screens: [EndScreen; 5] = [game_over, you_win, board_filled, cleared, time_up];
+        for end_screen in screens {
+            let mut screen = Screen::new(Vec::new());
+            end_screen(&mut screen, &game).unwrap();
+            let text = String::from_utf8_lossy(&screen.out).into_owned();
+            let lines: Vec<_> = text.split("\r\n").collect();
+            assert_eq!(lines.len(), 3, "{text:?}");
+            assert!(lines[1].starts_with("Score: 0"), "{text:?}");
+            assert_eq!(lines[2], "Seed: 1");
+        }
+    }
+}
@@ -0,0 +1,342 @@
+// Recording games tick by tick, and playing them back
+//
+// A recording is a text file, a header line, one line per tick, and an end
+// line:
+//
+//   snake-crossterm replay <format> <crate version> <seed>
+//   <delay in ms> <score> <position code>
+//   ...
+//   end <checksum>
+//
+// The checksum is the 64 bit FNV-1a hash of the tick lines, newlines and
+// all, in hex. A file without its end line was cut short, and one whose
+// checksum doesn't match has been changed since.
+//
+// Recordings in another format are refused. The crate version isn't checked:
+// frames are whole positions, which play back the same whatever version
+// made them, and anything that changes that bumps the format. It's only
+// there to say which version to play a refused recording back with.
+//
+// Format 3 shares positions in version 2 codes, format 2 recordings still
+// play back, as version 1 codes still decode.
+//
+// Frames are whole positions rather than key presses, so playback doesn't
+// depend on replaying the random numbers exactly.
+
+use std::fmt;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{poll, read, Event, KeyCode, KeyModifiers};
+
+use crate::game::{GameResult, GameState};
+use crate::observer::GameObserver;
+use crate::position::{Position, PositionError};
+use crate::render::render_replay;
+use crate::screen::Screen;
+
+const HEADER: &str = "snake-crossterm replay";
+
+// Bumped whenever recordings change in a way older versions can't read
+const FORMAT: &str = "3";
+
+// Older formats this version still reads
+const READABLE: &[&str] = &[FORMAT, "2"];
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+// Hash of the tick lines so far, see the top of this file
+#[derive(Debug, Clone, Copy)]
+struct Checksum(u64);
+
+impl Default for Checksum {
+    fn default() -> Self {
+        Checksum(FNV_OFFSET)
+    }
+}
+
+impl Checksum {
+    fn line(&mut self, line: &str) {
+        for byte in line.bytes().chain([b'\n']) {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+// Playback speeds to step through, NORMAL_SPEED is as recorded
+const SPEEDS: &[f64] = &[0.25, 0.5, 1.0, 2.0, 4.0];
+const NORMAL_SPEED: usize = 2;
+
+/// One tick of a recorded game
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// How long the tick lasted
+    pub delay: Duration,
+    pub score: u32,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayError {
+    NotAReplay,
+    /// Recorded in another format, by this version of the game if it's known
+    OtherFormat(Option<String>),
+    BadLine(usize),
+    BadPosition(usize, PositionError),
+    CutShort,
+    Changed,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::NotAReplay => write!(f, "not a replay file"),
+            ReplayError::OtherFormat(Some(version)) => write!(
+                f,
+                "recorded by snake-crossterm {version}, which this version can't play back"
+            ),
+            ReplayError::OtherFormat(None) => write!(
+                f,
+                "recorded by an older snake-crossterm, which this version can't play back"
+            ),
+            ReplayError::BadLine(line) => write!(f, "line {line} isn't a frame"),
+            ReplayError::BadPosition(line, e) => write!(f, "line {line}: {e}"),
+            ReplayError::CutShort => write!(f, "the recording is cut short"),
+            ReplayError::Changed => {
+                write!(f, "the recording has been changed since it was made")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Parse a recording made by [`Recorder`]
+///
+/// # Errors
+///
+/// Returns a [`ReplayError`] if the recording is in another format, cut
+/// short or changed since it was made, or for the first line that can't be
+/// read.
+pub fn parse(text: &str) -> Result<Vec<Frame>, ReplayError> {
+    let mut lines = text.lines();
+    let header = lines.next().unwrap_or_default();
+    let Some(fields) = header.strip_prefix(HEADER) else {
+        return Err(ReplayError::NotAReplay);
+    };
+    match fields.split_whitespace().collect::<Vec<_>>()[..] {
+        [format, _, seed] if READABLE.contains(&format) && seed.parse::<u64>().is_ok() => {}
+        [_, version, _] => return Err(ReplayError::OtherFormat(Some(version.to_string()))),
+        _ => return Err(ReplayError::OtherFormat(None)),
+    }
+
+    let mut checksum = Checksum::default();
+    let mut frames = Vec::new();
+    for (n, line) in (2..).zip(lines) {
+        if let Some(end) = line.strip_prefix("end ") {
+            if end.trim() != checksum.to_string() {
+                return Err(ReplayError::Changed);
+            }
+            return Ok(frames);
+        }
+        checksum.line(line);
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [delay, score, code] = fields[..] else {
+            return Err(ReplayError::BadLine(n));
+        };
+        frames.push(Frame {
+            delay: Duration::from_millis(delay.parse().map_err(|_| ReplayError::BadLine(n))?),
+            score: score.parse().map_err(|_| ReplayError::BadLine(n))?,
+            position: Position::decode(code).map_err(|e| ReplayError::BadPosition(n, e))?,
+        });
+    }
+    Err(ReplayError::CutShort)
+}
+
+/// Writes a recording of the game as it's played
+pub struct Recorder<W: Write> {
+    out: W,
+    checksum: Checksum,
+}
+
+impl<W: Write> Recorder<W> {
+    /// Start a recording of a game played from `seed`, writing its header
+    /// straight away
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from writing the header.
+    pub fn new(mut out: W, seed: u64) -> io::Result<Self> {
+        let version = env!("CARGO_PKG_VERSION");
+        writeln!(out, "{HEADER} {FORMAT} {version} {seed}")?;
+        Ok(Recorder {
+            out,
+            checksum: Checksum::default(),
+        })
+    }
+}
+
+// A recording that fails part way is cut short, rather than ending the game
+impl<W: Write> GameObserver for Recorder<W> {
+    fn tick(&mut self, game: &GameState) {
+        // Every board the game allows encodes, but a tick that somehow
+        // doesn't is left out rather than ending the game
+        let Ok(code) = Position::from_game(game).encode() else {
+            return;
+        };
+        let line = format!("{} {} {code}", game.tick_delay().as_millis(), game.score);
+        self.checksum.line(&line);
+        writeln!(self.out, "{line}").ok();
+    }
+
+    fn finish(&mut self, _game: &GameState, _result: GameResult) {
+        writeln!(self.out, "end {}", self.checksum).ok();
+        self.out.flush().ok();
+    }
+}
+
+// Where playback is up to
+struct Playback {
+    frame: usize,
+    speed: usize,
+    paused: bool,
+}
+
+impl Playback {
+    fn status(&self, frames: usize) -> String {
+        let state = if self.paused {
+            "paused".to_string()
+        } else {
+            format!("x{}", SPEEDS[self.speed])
+        };
+        format!("Replay {}/{frames}  {state}", self.frame + 1)
+    }
+}
+
+/// Play back `frames`, drawn with the styles and colors of `game`, until
+/// they run out or the viewer quits
+///
+/// Space pauses, `.` steps a frame while paused, `+` and `-` change the
+/// speed, and `q`, `Esc` or Ctrl-C stop.
+///
+/// # Errors
+///
+/// Returns any error from reading keys or drawing frames.
+pub fn play<O: Write>(game: &mut GameState, frames: &[Frame], out: &mut O) -> io::Result<()> {
+    // Recorded ticks carry the final score, the bonus doesn't apply again
+    game.speed_bonus = 0.0;
+    let mut screen = Screen::new(out);
+    let mut playback = Playback {
+        frame: 0,
+        speed: NORMAL_SPEED,
+        paused: false,
+    };
+
+    while playback.frame < frames.len() {
+        let frame = &frames[playback.frame];
+        game.load_position(frame.position.clone());
+        game.score = frame.score;
+        render_replay(&mut screen, game, &playback.status(frames.len()))?;
+
+        // Wait out the frame, or until a key changes something
+        let until = Instant::now() + frame.delay.div_f64(SPEEDS[playback.speed]);
+        loop {
+            // In seconds, Duration::from_mins is too new for older toolchains
+            #[allow(clippy::duration_suboptimal_units)]
+            let timeout = if playback.paused {
+                Duration::from_secs(60)
+            } else {
+                until.saturating_duration_since(Instant::now())
+            };
+            if !poll(timeout)? {
+                if playback.paused {
+                    continue;
+                }
+                playback.frame += 1;
+                break;
+            }
+            let Event::Key(k) = read()? else {
+                continue;
+            };
+            match k.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('c') if k.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                KeyCode::Char(' ') => playback.paused = !playback.paused,
+                KeyCode::Char('.') if playback.paused => playback.frame += 1,
+                KeyCode::Char('+' | '=') => {
+                    playback.speed = (playback.speed + 1).min(SPEEDS.len() - 1);
+                }
+                KeyCode::Char('-') => playback.speed = playback.speed.saturating_sub(1),
+                _ => continue,
+            }
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::GameStateBuilder;
+
+    // A recording of a few ticks, the score going up each one
+    fn recording() -> String {
+        let mut game = GameStateBuilder::new().seed(7).build().unwrap();
+        let mut recorder = Recorder::new(Vec::new(), game.seed).unwrap();
+        for score in 0..3 {
+            game.score = score;
+            recorder.tick(&game);
+        }
+        recorder.finish(&game, GameResult::Quit);
+        String::from_utf8(recorder.out).unwrap()
+    }
+
+    #[test]
+    fn recordings_play_back() {
+        let frames = parse(&recording()).unwrap();
+        let scores: Vec<u32> = frames.iter().map(|f| f.score).collect();
+        assert_eq!(scores, [0, 1, 2]);
+        let game = GameStateBuilder::new().seed(7).build().unwrap();
+        assert_eq!(frames[0].position.apple, game.apple);
+        assert_eq!(frames[0].position.snake.len(), game.snake.len());
+    }
+
+    #[test]
+    fn changed_recordings_are_refused() {
+        let text = recording().replacen(" 1 ", " 9 ", 1);
+        assert_eq!(parse(&text).unwrap_err(), ReplayError::Changed);
+    }
+
+    #[test]
+    fn cut_short_recordings_are_refused() {
+        let text = recording();
+        let cut = &text[..text.rfind("end").unwrap()];
+        assert_eq!(parse(cut).unwrap_err(), ReplayError::CutShort);
+    }
+
+    #[test]
+    fn other_formats_are_refused() {
+        let text = recording().replacen(&format!("{HEADER} {FORMAT} "), &format!("{HEADER} 1 "), 1);
+        let version = env!("CARGO_PKG_VERSION").to_string();
+        assert_eq!(
+            parse(&text).unwrap_err(),
+            ReplayError::OtherFormat(Some(version))
+        );
+    }
+
+    #[test]
+    fn other_crate_versions_in_the_same_format_play_back() {
+        let header = format!("{HEADER} {FORMAT} {}", env!("CARGO_PKG_VERSION"));
+        let text = recording().replacen(&header, &format!("{HEADER} {FORMAT} 0.0.1"), 1);
+        assert_eq!(parse(&text).unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,288 @@
+// Frames composed off screen, then drawn to the terminal by diffing them
+// against the frame before, so a tick only writes the cells that changed
+
+use std::io::{self, Write};
+
+use crossterm::cursor::MoveTo;
+use crossterm::queue;
+use crossterm::style::{style, Color, Stylize};
+use crossterm::terminal::{Clear, ClearType};
+
+// What's in one terminal cell, no color is the terminal's own
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Cell {
+    glyph: char,
+    color: Option<Color>,
+    background: Option<Color>,
+}
+
+const BLANK: Cell = Cell {
+    glyph: ' ',
+    color: None,
+    background: None,
+};
+
+// A frame being composed, rows grow to fit whatever is put in them
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Canvas {
+    rows: Vec<Vec<Cell>>,
+}
+
+impl Canvas {
+    pub(crate) fn put(&mut self, at: (u16, u16), glyph: char, color: Option<Color>) {
+        let background = None;
+        self.set(
+            at,
+            Cell {
+                glyph,
+                color,
+                background,
+            },
+        );
+    }
+
+    // Blank a cell, with a background color
+    pub(crate) fn tint(&mut self, at: (u16, u16), background: Color) {
+        let background = Some(background);
+        self.set(
+            at,
+            Cell {
+                background,
+                ..BLANK
+            },
+        );
+    }
+
+    fn set(&mut self, (x, y): (u16, u16), cell: Cell) {
+        let (x, y) = (usize::from(x), usize::from(y));
+        if self.rows.len() <= y {
+            self.rows.resize(y + 1, Vec::new());
+        }
+        let row = &mut self.rows[y];
+        if row.len() <= x {
+            row.resize(x + 1, BLANK);
+        }
+        row[x] = cell;
+    }
+
+    // Write text from a cell rightwards, returning the column after it
+    pub(crate) fn text(&mut self, (x, y): (u16, u16), text: &str, color: Option<Color>) -> u16 {
+        let mut x = x;
+        for glyph in text.chars() {
+            self.put((x, y), glyph, color);
+            x += 1;
+        }
+        x
+    }
+
+    // Move everything right and down, leaving blanks behind, but only as far
+    // as it still fits in `within`
+    pub(crate) fn shift(&mut self, (dx, dy): (u16, u16), within: (u16, u16)) {
+        let widest = (0..self.height()).map(|y| self.width(y)).max().unwrap_or(0);
+        let dx = dx.min(within.0.saturating_sub(widest));
+        let dy = dy.min(within.1.saturating_sub(self.height()));
+        for row in &mut self.rows {
+            row.splice(0..0, std::iter::repeat_n(BLANK, usize::from(dx)));
+        }
+        self.rows
+            .splice(0..0, std::iter::repeat_n(Vec::new(), usize::from(dy)));
+    }
+
+    // What's drawn at a spot, for checking frames in tests
+    #[cfg(test)]
+    pub(crate) fn glyph(&self, (x, y): (u16, u16)) -> char {
+        self.get(x, y).glyph
+    }
+
+    fn get(&self, x: u16, y: u16) -> Cell {
+        self.rows
+            .get(usize::from(y))
+            .and_then(|row| row.get(usize::from(x)))
+            .copied()
+            .unwrap_or(BLANK)
+    }
+
+    fn height(&self) -> u16 {
+        u16::try_from(self.rows.len()).unwrap()
+    }
+
+    fn width(&self, y: u16) -> u16 {
+        self.rows
+            .get(usize::from(y))
+            .map_or(0, |row| u16::try_from(row.len()).unwrap())
+    }
+}
+
+// The terminal, and the frame last drawn on it
+pub(crate) struct Screen<O: Write> {
+    pub(crate) out: O,
+    last: Option<Canvas>,
+}
+
+impl<O: Write> Screen<O> {
+    pub(crate) fn new(out: O) -> Self {
+        Screen { out, last: None }
+    }
+
+    // Forget what's on the terminal so the next frame is drawn in full, for
+    // when it might have been disturbed, like after a resize
+    pub(crate) fn invalidate(&mut self) {
+        self.last = None;
+    }
+
+    // Draw a frame, moving the cursor once for each run of changed cells in a
+    // row, and styling each stretch of one color and background in the run
+    // once
+    pub(crate) fn present(&mut self, frame: Canvas) -> io::Result<()> {
+        let last = if let Some(last) = self.last.take() {
+            last
+        } else {
+            queue!(self.out, Clear(ClearType::All))?;
+            Canvas::default()
+        };
+
+        for y in 0..frame.height().max(last.height()) {
+            let width = frame.width(y).max(last.width(y));
+            let changed = |x: u16| frame.get(x, y) != last.get(x, y);
+            let mut x = 0;
+            while x < width {
+                if !changed(x) {
+                    x += 1;
+                    continue;
+                }
+                queue!(self.out, MoveTo(x, y))?;
+                while x < width && changed(x) {
+                    let Cell {
+                        color, background, ..
+                    } = frame.get(x, y);
+                    let same = |cell: Cell| cell.color == color && cell.background == background;
+                    let mut text = String::new();
+                    while x < width && changed(x) && same(frame.get(x, y)) {
+                        text.push(frame.get(x, y).glyph);
+                        x += 1;
+                    }
+                    let mut styled = style(text);
+                    if let Some(color) = color {
+                        styled = styled.with(color);
+                    }
+                    if let Some(background) = background {
+                        styled = styled.on(background);
+                    }
+                    write!(self.out, "{styled}")?;
+                }
+            }
+        }
+
+        self.out.flush()?;
+        self.last = Some(frame);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    // What a terminal shows after the bytes written to it, leaving out blank
+    // cells with no background
+    type Shown = HashMap<(u16, u16), (char, Option<String>, Option<String>)>;
+
+    // Just enough of a terminal for what `present` writes: clearing, moving
+    // the cursor, and setting and resetting colors
+    fn show(shown: &mut Shown, bytes: &[u8]) {
+        let text = std::str::from_utf8(bytes).unwrap();
+        let (mut x, mut y) = (0, 0);
+        let (mut color, mut background) = (None, None);
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c != '\x1b' {
+                if c == ' ' && background.is_none() {
+                    shown.remove(&(x, y));
+                } else {
+                    shown.insert((x, y), (c, color.clone(), background.clone()));
+                }
+                x += 1;
+                continue;
+            }
+            assert_eq!(chars.next(), Some('['));
+            let mut params = String::new();
+            let end = loop {
+                let c = chars.next().unwrap();
+                if c.is_ascii_alphabetic() {
+                    break c;
+                }
+                params.push(c);
+            };
+            match end {
+                'J' => shown.clear(),
+                'H' => {
+                    let (row, column) = params.split_once(';').unwrap();
+                    (x, y) = (
+                        column.parse::<u16>().unwrap() - 1,
+                        row.parse::<u16>().unwrap() - 1,
+                    );
+                }
+                'm' if params.starts_with("38;") => color = Some(params),
+                'm' if params.starts_with("48;") => background = Some(params),
+                'm' if params == "39" => color = None,
+                'm' if params == "49" => background = None,
+                'm' if params == "0" => (color, background) = (None, None),
+                _ => panic!("unexpected escape {params}{end}"),
+            }
+        }
+    }
+
+    fn frames() -> Vec<Canvas> {
+        let mut first = Canvas::default();
+        first.text((0, 0), "Score: 10", None);
+        first.text((2, 2), "oooo@", Some(Color::Green));
+        first.put((6, 4), '*', Some(Color::Red));
+        first.tint((1, 5), Color::Blue);
+
+        let mut second = first.clone();
+        second.text((0, 0), "Score: 9", None);
+        second.text((2, 2), " ooo", Some(Color::Green));
+        second.put((6, 2), '@', Some(Color::Yellow));
+        second.put((6, 4), '*', Some(Color::Magenta));
+
+        // Shorter than the last, with what's left off cleared
+        let mut third = Canvas::default();
+        third.text((0, 0), "Game over", Some(Color::Red));
+        third.text((3, 2), "ooo@", Some(Color::Green));
+        vec![first, second, third]
+    }
+
+    #[test]
+    fn diffed_frames_match_a_full_redraw() {
+        let mut diffed = Shown::new();
+        let mut screen = Screen::new(Vec::new());
+        for frame in frames() {
+            screen.present(frame).unwrap();
+            show(&mut diffed, &screen.out);
+            screen.out.clear();
+        }
+
+        let mut full = Shown::new();
+        let mut fresh = Screen::new(Vec::new());
+        fresh.present(frames().pop().unwrap()).unwrap();
+        show(&mut full, &fresh.out);
+        assert_eq!(diffed, full);
+
+        // Drawn again in full once invalidated, over whatever was there
+        screen.invalidate();
+        screen.present(frames().pop().unwrap()).unwrap();
+        let mut redrawn = diffed.clone();
+        show(&mut redrawn, &screen.out);
+        assert_eq!(redrawn, full);
+    }
+
+    #[test]
+    fn unchanged_frames_write_nothing() {
+        let mut screen = Screen::new(Vec::new());
+        screen.present(frames().remove(0)).unwrap();
+        screen.out.clear();
+        screen.present(frames().remove(0)).unwrap();
+        assert!(screen.out.is_empty());
+    }
+}
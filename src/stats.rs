@@ -0,0 +1,300 @@
+// Per-game statistics, appended to a CSV file, and totals for the games
+// played since the program started
+
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::daily;
+use crate::game::GameState;
+
+// Logs from before modes were recorded lack the last two columns, and count
+// as casual, and those from before names lack the last one
+const HEADER: &str = "timestamp,score,length,apples,duration_secs,width,height,delay_ms,mode,name";
+
+/// Longest name that goes with a high score, in characters
+pub const NAME_LEN: usize = 12;
+
+/// How many games each leaderboard keeps
+pub const HIGH_SCORES: usize = 10;
+
+/// Which leaderboard a game counts towards
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Casual,
+    Hardcore,
+    Tron,
+    Daily,
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mode::Casual => write!(f, "casual"),
+            Mode::Hardcore => write!(f, "hardcore"),
+            Mode::Tron => write!(f, "tron"),
+            Mode::Daily => write!(f, "daily"),
+        }
+    }
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "casual" => Ok(Mode::Casual),
+            "hardcore" => Ok(Mode::Hardcore),
+            "tron" => Ok(Mode::Tron),
+            "daily" => Ok(Mode::Daily),
+            _ => Err(format!(
+                "unknown mode '{s}', expected casual, hardcore, tron or daily"
+            )),
+        }
+    }
+}
+
+/// One row of the statistics log
+#[derive(Debug, Clone)]
+pub struct GameRecord {
+    /// Seconds since the Unix epoch when the game ended
+    pub timestamp: u64,
+    pub score: u32,
+    pub length: usize,
+    pub apples: u32,
+    pub duration_secs: u64,
+    pub board: (u16, u16),
+    /// Tick delay at the end of the game, standing in for difficulty
+    pub delay_ms: u128,
+    pub mode: Mode,
+    /// Entered for a high score, empty if there wasn't one or it was skipped
+    pub name: String,
+}
+
+impl GameRecord {
+    #[must_use]
+    pub fn from_game(game: &GameState) -> Self {
+        GameRecord {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            score: game.score,
+            length: game.snake.len(),
+            apples: game.apples,
+            duration_secs: game.elapsed().as_secs(),
+            board: game.board,
+            delay_ms: game.delay.as_millis(),
+            // Tron scores count ticks, not apples, so they're kept apart, and
+            // daily games are only fair against the same day's board
+            mode: if game.tron {
+                Mode::Tron
+            } else if game.daily {
+                Mode::Daily
+            } else if game.hardcore {
+                Mode::Hardcore
+            } else {
+                Mode::Casual
+            },
+            name: String::new(),
+        }
+    }
+
+    // A row of the log, None if it's the header or mangled
+    fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.trim().split(',').collect();
+        if !(8..=10).contains(&fields.len()) {
+            return None;
+        }
+        Some(GameRecord {
+            timestamp: fields[0].parse().ok()?,
+            score: fields[1].parse().ok()?,
+            length: fields[2].parse().ok()?,
+            apples: fields[3].parse().ok()?,
+            duration_secs: fields[4].parse().ok()?,
+            board: (fields[5].parse().ok()?, fields[6].parse().ok()?),
+            delay_ms: fields[7].parse().ok()?,
+            mode: match fields.get(8) {
+                Some(mode) => mode.parse().ok()?,
+                None => Mode::Casual,
+            },
+            name: fields.get(9).map_or(String::new(), ToString::to_string),
+        })
+    }
+}
+
+// A row of a leaderboard, after its rank
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:>6}  length {:<4} {:>7}  {:>4}ms  {}",
+            self.score,
+            self.length,
+            format!("{}x{}", self.board.0, self.board.1),
+            self.delay_ms,
+            daily::date(self.timestamp)
+        )?;
+        if !self.name.is_empty() {
+            write!(f, "  {}", self.name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether a character can go in a name, which can't hold the log's commas
+#[must_use]
+pub fn name_char(c: char) -> bool {
+    c != ',' && !c.is_control()
+}
+
+/// Totals for the games played since the program started, only ever kept in
+/// memory
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    pub games: u32,
+    pub best: u32,
+    pub total_score: u64,
+    pub apples: u32,
+}
+
+impl Session {
+    /// Count a finished game
+    pub fn record(&mut self, game: &GameState) {
+        self.games += 1;
+        self.best = self.best.max(game.score);
+        self.total_score += u64::from(game.score);
+        self.apples += game.apples;
+    }
+
+    /// Mean score per game, 0 before any have been played
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn average(&self) -> f64 {
+        if self.games == 0 {
+            0.0
+        } else {
+            self.total_score as f64 / f64::from(self.games)
+        }
+    }
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Games: {}  Best: {}  Average: {:.0}  Apples: {}",
+            self.games,
+            self.best,
+            self.average(),
+            self.apples
+        )
+    }
+}
+
+/// `stats.csv` in the user's data directory, if there is one
+#[must_use]
+pub fn default_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("snake-crossterm").join("stats.csv"))
+}
+
+/// Append a record to the log at `path`, creating it with a header first if
+/// it doesn't exist yet
+///
+/// # Errors
+///
+/// Returns any error from creating or writing the file.
+pub fn append(path: &Path, record: &GameRecord) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    if file.metadata()?.len() == 0 {
+        writeln!(file, "{HEADER}")?;
+    }
+    writeln!(
+        file,
+        "{},{},{},{},{},{},{},{},{},{}",
+        record.timestamp,
+        record.score,
+        record.length,
+        record.apples,
+        record.duration_secs,
+        record.board.0,
+        record.board.1,
+        record.delay_ms,
+        record.mode,
+        record
+            .name
+            .chars()
+            .filter(|&c| name_char(c))
+            .take(NAME_LEN)
+            .collect::<String>()
+    )
+}
+
+/// Read every record in the log at `path`, skipping lines that can't be read
+///
+/// # Errors
+///
+/// Returns any error from reading the file.
+pub fn load(path: &Path) -> io::Result<Vec<GameRecord>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter_map(GameRecord::parse)
+        .collect())
+}
+
+/// Whether a game would make its mode's leaderboard, ties going to the games
+/// played first
+#[must_use]
+pub fn high_score(records: &[GameRecord], record: &GameRecord) -> bool {
+    let best = leaderboard(records, record.mode, HIGH_SCORES);
+    best.len() < HIGH_SCORES || best.last().is_some_and(|last| record.score > last.score)
+}
+
+/// The best `count` records played in `mode`, highest score first
+#[must_use]
+pub fn leaderboard(records: &[GameRecord], mode: Mode, count: usize) -> Vec<&GameRecord> {
+    let mut board: Vec<_> = records.iter().filter(|r| r.mode == mode).collect();
+    board.sort_by(|a, b| b.score.cmp(&a.score).then(a.timestamp.cmp(&b.timestamp)));
+    board.truncate(count);
+    board
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::GameStateBuilder;
+
+    #[test]
+    fn daily_games_have_their_own_leaderboard() {
+        let casual = GameStateBuilder::new().seed(1).build().unwrap();
+        let daily = GameStateBuilder::new()
+            .seed(daily::seed_for("2026-10-16"))
+            .daily(true)
+            .hardcore(true)
+            .build()
+            .unwrap();
+        let records = [
+            GameRecord::from_game(&casual),
+            GameRecord::from_game(&daily),
+        ];
+        assert_eq!(records[0].mode, Mode::Casual);
+        assert_eq!(records[1].mode, Mode::Daily);
+
+        let best = leaderboard(&records, Mode::Daily, HIGH_SCORES);
+        assert_eq!(best.len(), 1);
+        assert_eq!(best[0].mode, Mode::Daily);
+    }
+
+    #[test]
+    fn daily_mode_is_logged_and_read_back() {
+        assert_eq!("daily".parse::<Mode>(), Ok(Mode::Daily));
+        let record = GameRecord::parse("1,5,4,2,30,20,10,150,daily,ann").unwrap();
+        assert_eq!(record.mode, Mode::Daily);
+        assert_eq!(record.mode.to_string(), "daily");
+    }
+}
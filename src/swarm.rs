@@ -0,0 +1,360 @@
+// Computer controlled snakes sharing the board with the player
+//
+// Each tick, after the player has moved, every rival heads along the shortest
+// path to the nearest apple, treating walls and every snake as blocked. Paths
+// step off the board wherever its edges wrap, just as the snakes can, so a
+// route through a wrapping edge is taken when it's shorter. A rival that runs
+// into anything is gone for good, and outlasting them all wins the game.
+//
+// Choices are always the same on the same board. Of equally short paths, the
+// one starting north wins, then south, east and west, the order of DIRECTIONS.
+// With no path, a rival picks an open direction using the game's random
+// numbers, which come from its seed.
+//
+// The rival skill changes how a rival picks. An easy rival leaves its path for
+// a random open direction now and then. A hard one won't take a path whose
+// first step leads into fewer free cells than it's long, and with no path to
+// take it heads for the most room rather than anywhere open. The autopilot
+// always plays like a hard rival.
+//
+// In tron mode there are no apples to head for, so every rival picks as it
+// would with no path, and none of them ever shrink.
+
+use std::collections::{HashSet, VecDeque};
+
+use crossterm::style::Color;
+use rand::prelude::*;
+
+use crate::game::{
+    next_head, Direction, GameState, RivalSkill, Segment, SegmentType, Snake, APPLE_POINTS,
+};
+
+// Rival colors, handed out in turn
+const COLORS: &[Color] = &[
+    Color::Magenta,
+    Color::Cyan,
+    Color::Yellow,
+    Color::Blue,
+    Color::DarkMagenta,
+    Color::DarkCyan,
+];
+
+// How close (Chebyshev) a rival may start to the player's head
+const SPAWN_DISTANCE: u16 = 3;
+
+// Chance each tick of an easy rival wandering off its path
+const WANDER_CHANCE: f64 = 0.25;
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::North,
+    Direction::South,
+    Direction::East,
+    Direction::West,
+];
+
+impl GameState {
+    // Whether a cell is taken by any rival
+    pub(crate) fn on_rival(&self, cell: (u16, u16)) -> bool {
+        self.rivals
+            .iter()
+            .any(|rival| rival.body.iter().any(|s| (s.0, s.1) == cell))
+    }
+
+    // Place `count` rivals heading east, each with an apple of its own to
+    // compete for, keeping clear of the player's head
+    // Fewer are placed if the board runs out of room
+    pub(crate) fn spawn_rivals(&mut self, count: usize, rng: &mut impl Rng) {
+        for i in 0..count {
+            let head = self.head;
+            let starts: Vec<(u16, u16)> = (1..self.board.0)
+                .flat_map(|x| (0..self.board.1).map(move |y| (x, y)))
+                .filter(|&(x, y)| {
+                    x.abs_diff(head.0).max(y.abs_diff(head.1)) >= SPAWN_DISTANCE
+                        && self.is_free((x, y))
+                        && self.is_free((x - 1, y))
+                })
+                .collect();
+            let Some(&(x, y)) = starts.choose(rng) else {
+                break;
+            };
+            self.rivals.push(Snake {
+                body: VecDeque::from([
+                    Segment(x - 1, y, SegmentType::EastWest, Direction::East),
+                    Segment(x, y, SegmentType::EastWest, Direction::East),
+                ]),
+                color: COLORS[i % COLORS.len()],
+            });
+            if let Some(apple) = self.random_apple_cell(rng) {
+                self.spare_apples.push(apple);
+            }
+        }
+    }
+
+    // The player eating one of the rivals' apples, which scores like any other
+    // Returns whether there was one to eat
+    pub(crate) fn eat_spare(&mut self, cell: (u16, u16), rng: &mut impl Rng) -> bool {
+        let Some(spare) = self.spare_apples.iter().position(|&a| a == cell) else {
+            return false;
+        };
+        self.score += self.apple_points(APPLE_POINTS);
+        self.apples += 1;
+        if let Some(apple) = self.random_apple_cell(rng) {
+            self.spare_apples[spare] = apple;
+        }
+        true
+    }
+
+    // Whether every rival there was has crashed
+    pub(crate) fn outlasted_rivals(&self) -> bool {
+        self.swarm > 0 && self.rivals.is_empty()
+    }
+
+    // Move every rival a step, dropping those that crash
+    pub(crate) fn move_rivals(&mut self, rng: &mut impl Rng) {
+        for i in 0..self.rivals.len() {
+            let last = self.rivals[i].body[self.rivals[i].body.len() - 1];
+            let head = (last.0, last.1);
+            let len = self.rivals[i].body.len();
+            let direction = match self.rival_skill {
+                _ if self.tron => None,
+                RivalSkill::Easy if rng.gen_bool(WANDER_CHANCE) => None,
+                RivalSkill::Easy | RivalSkill::Normal => self.path_to_apple(head),
+                RivalSkill::Hard => self
+                    .path_to_apple(head)
+                    .filter(|&d| self.room(head, d, len) >= len),
+            }
+            .or_else(|| match self.rival_skill {
+                RivalSkill::Hard => self.roomiest(head, len),
+                RivalSkill::Easy | RivalSkill::Normal => DIRECTIONS
+                    .into_iter()
+                    .filter(|&d| self.open(head, d).is_some())
+                    .choose(rng),
+            });
+
+            // Boxed in, or crashing into something, ends a rival
+            let Some((direction, cell)) =
+                direction.and_then(|d| self.open(head, d).map(|cell| (d, cell)))
+            else {
+                self.rivals[i].body.clear();
+                continue;
+            };
+
+            let rival = &mut self.rivals[i];
+            let len = rival.body.len();
+            rival.body[len - 1].2 = SegmentType::from_next(rival.body[len - 1].3, direction);
+            rival.body.push_back(Segment(
+                cell.0,
+                cell.1,
+                SegmentType::from_dir(direction),
+                direction,
+            ));
+
+            // Eating grows the rival, and the apple comes back elsewhere
+            if self.tron {
+                // Rivals leave their whole trail behind too
+            } else if cell == self.apple {
+                if let Some(apple) = self.random_apple_cell(rng) {
+                    self.apple = apple;
+                }
+                self.roll_ghost(rng);
+            } else if let Some(spare) = self.spare_apples.iter().position(|&a| a == cell) {
+                if let Some(apple) = self.random_apple_cell(rng) {
+                    self.spare_apples[spare] = apple;
+                }
+            } else {
+                self.rivals[i].body.pop_front();
+            }
+            let rival = &mut self.rivals[i];
+            let next = rival.body[1].3;
+            rival.body[0].2 = SegmentType::from_dir(next);
+        }
+        self.rivals.retain(|rival| {
+            !rival.body.is_empty() && !rival.body.iter().any(|s| self.walls.contains(&(s.0, s.1)))
+        });
+    }
+
+    // Steer the player's snake like a hard rival, along the shortest path to
+    // an apple unless it leads somewhere too small, or else towards the most
+    // room, or straight on if it's boxed in
+    pub(crate) fn steer(&mut self) {
+        let len = self.snake.len();
+        if let Some(direction) = (!self.tron)
+            .then(|| self.path_to_apple(self.head))
+            .flatten()
+            .filter(|&d| self.room(self.head, d, len) >= len)
+            .or_else(|| self.roomiest(self.head, len))
+        {
+            self.direction = direction;
+        }
+    }
+
+    // Where a step from a cell lands, if nothing's there
+    fn open(&self, cell: (u16, u16), direction: Direction) -> Option<(u16, u16)> {
+        let (next, _) = next_head(cell, self.board, direction, self.edges)?;
+        (!self.walls.contains(&next)
+            && !self.blocks.contains(&next)
+            && !self.on_snake(next)
+            && !self.on_rival(next))
+        .then_some(next)
+    }
+
+    // Free cells reachable from a step off a cell, counting no further than
+    // `limit`
+    fn room(&self, cell: (u16, u16), direction: Direction, limit: usize) -> usize {
+        let Some(start) = self.open(cell, direction) else {
+            return 0;
+        };
+        let mut seen = HashSet::from([cell, start]);
+        let mut queue = VecDeque::from([start]);
+        let mut count = 0;
+        while let Some(cell) = queue.pop_front() {
+            count += 1;
+            if count >= limit {
+                break;
+            }
+            for d in DIRECTIONS {
+                if let Some(next) = self.open(cell, d) {
+                    if seen.insert(next) {
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    // The open direction from a cell with the most room past it, up to
+    // `limit`, the first in DIRECTIONS on a tie
+    fn roomiest(&self, cell: (u16, u16), limit: usize) -> Option<Direction> {
+        DIRECTIONS
+            .into_iter()
+            .rev()
+            .filter(|&d| self.open(cell, d).is_some())
+            .max_by_key(|&d| self.room(cell, d, limit))
+    }
+
+    // First step of the shortest path from a cell to any apple
+    fn path_to_apple(&self, from: (u16, u16)) -> Option<Direction> {
+        let mut seen = HashSet::from([from]);
+        let mut queue: VecDeque<((u16, u16), Direction)> = DIRECTIONS
+            .into_iter()
+            .filter_map(|d| self.open(from, d).map(|cell| (cell, d)))
+            .filter(|&(cell, _)| seen.insert(cell))
+            .collect();
+        while let Some((cell, first)) = queue.pop_front() {
+            if cell == self.apple || self.spare_apples.contains(&cell) {
+                return Some(first);
+            }
+            for d in DIRECTIONS {
+                if let Some(next) = self.open(cell, d) {
+                    if seen.insert(next) {
+                        queue.push_back((next, first));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::GameStateBuilder;
+    use rand::rngs::StdRng;
+
+    // The player heading north from (1, 5), with the apple at (8, 5), nearer
+    // the other way round through the west edge
+    fn across(wrap: bool) -> GameState {
+        let mut game = GameStateBuilder::new()
+            .seed(1)
+            .wrap(wrap)
+            .apple(8, 5)
+            .build()
+            .unwrap();
+        game.snake = VecDeque::from([
+            Segment(1, 6, SegmentType::NorthSouth, Direction::North),
+            Segment(1, 5, SegmentType::NorthSouth, Direction::North),
+        ]);
+        game.head = (1, 5);
+        game
+    }
+
+    // Steps taken following the path to the apple from the head
+    fn steps_to_apple(game: &GameState) -> usize {
+        let mut cell = game.head;
+        let mut steps = 0;
+        while cell != game.apple {
+            let direction = game.path_to_apple(cell).expect("a path to the apple");
+            cell = next_head(cell, game.board, direction, game.edges)
+                .unwrap()
+                .0;
+            steps += 1;
+        }
+        steps
+    }
+
+    #[test]
+    fn paths_go_through_wrapping_edges() {
+        let game = across(true);
+        assert_eq!(game.path_to_apple(game.head), Some(Direction::West));
+        assert_eq!(steps_to_apple(&game), 3);
+    }
+
+    #[test]
+    fn paths_go_round_without_wrapping() {
+        let game = across(false);
+        assert_eq!(game.path_to_apple(game.head), Some(Direction::East));
+        assert_eq!(steps_to_apple(&game), 7);
+    }
+
+    // The player at (5, 5) heading east, with the apple at `apple`
+    fn facing(apple: (u16, u16)) -> GameState {
+        let mut game = GameStateBuilder::new()
+            .seed(1)
+            .apple(apple.0, apple.1)
+            .build()
+            .unwrap();
+        game.snake = VecDeque::from([
+            Segment(4, 5, SegmentType::EastWest, Direction::East),
+            Segment(5, 5, SegmentType::EastWest, Direction::East),
+        ]);
+        game.head = (5, 5);
+        game
+    }
+
+    #[test]
+    fn equally_short_paths_go_in_direction_order() {
+        // North before east, and south before east
+        assert_eq!(facing((7, 3)).path_to_apple((5, 5)), Some(Direction::North));
+        assert_eq!(facing((7, 7)).path_to_apple((5, 5)), Some(Direction::South));
+        let mut game = facing((7, 7));
+        game.steer();
+        assert_eq!(game.direction, Direction::South);
+    }
+
+    #[test]
+    fn rivals_move_the_same_on_the_same_board() {
+        let moves = || {
+            let mut game = GameStateBuilder::new()
+                .seed(3)
+                .board(20, 20)
+                .swarm(3)
+                .build()
+                .unwrap();
+            let mut rng = StdRng::seed_from_u64(3);
+            (0..30)
+                .map(|_| {
+                    game.move_rivals(&mut rng);
+                    game.rivals
+                        .iter()
+                        .map(|r| r.body.iter().map(|s| (s.0, s.1)).collect::<Vec<_>>())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        let first = moves();
+        assert!(first.iter().any(|rivals| !rivals.is_empty()));
+        assert_eq!(first, moves());
+    }
+}